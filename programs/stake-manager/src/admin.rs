@@ -1,10 +1,15 @@
-use crate::{Errors, StakeManager};
-use anchor_lang::{prelude::*, system_program};
+use crate::{
+    EraHistory, EraProcessData, Errors, LegacyStakeManager, LegacyUnstakeAccount, StakeManager,
+    UnstakeAccount, ValidatorEntry, ValidatorRegistry, ValidatorStatus, VoteAccountState,
+    MAX_VALIDATORS,
+};
+use anchor_lang::{prelude::*, system_program, Discriminator};
 #[derive(Accounts)]
 pub struct TransferAdmin<'info> {
     #[account(
         mut, 
-        has_one = admin @ Errors::AdminNotMatch
+        has_one = admin @ Errors::AdminNotMatch,
+        constraint = stake_manager.is_upgraded() @ Errors::StakeManagerNotUpgraded
     )]
     pub stake_manager: Box<Account<'info, StakeManager>>,
 
@@ -24,7 +29,8 @@ impl<'info> TransferAdmin<'info> {
 pub struct TransferBalancer<'info> {
     #[account(
         mut, 
-        has_one = admin @ Errors::AdminNotMatch
+        has_one = admin @ Errors::AdminNotMatch,
+        constraint = stake_manager.is_upgraded() @ Errors::StakeManagerNotUpgraded
     )]
     pub stake_manager: Box<Account<'info, StakeManager>>,
 
@@ -44,7 +50,8 @@ impl<'info> TransferBalancer<'info> {
 pub struct TransferFeeRecipient<'info> {
     #[account(
         mut, 
-        has_one = admin @ Errors::AdminNotMatch
+        has_one = admin @ Errors::AdminNotMatch,
+        constraint = stake_manager.is_upgraded() @ Errors::StakeManagerNotUpgraded
     )]
     pub stake_manager: Box<Account<'info, StakeManager>>,
 
@@ -64,7 +71,8 @@ impl<'info> TransferFeeRecipient<'info> {
 pub struct SetMinStakeAmount<'info> {
     #[account(
         mut, 
-        has_one = admin @ Errors::AdminNotMatch
+        has_one = admin @ Errors::AdminNotMatch,
+        constraint = stake_manager.is_upgraded() @ Errors::StakeManagerNotUpgraded
     )]
     pub stake_manager: Box<Account<'info, StakeManager>>,
 
@@ -84,7 +92,8 @@ impl<'info> SetMinStakeAmount<'info> {
 pub struct SetUnbondingDuration<'info> {
     #[account(
         mut, 
-        has_one = admin @ Errors::AdminNotMatch
+        has_one = admin @ Errors::AdminNotMatch,
+        constraint = stake_manager.is_upgraded() @ Errors::StakeManagerNotUpgraded
     )]
     pub stake_manager: Box<Account<'info, StakeManager>>,
 
@@ -104,7 +113,8 @@ impl<'info> SetUnbondingDuration<'info> {
 pub struct SetUnstakeFeeCommission<'info> {
     #[account(
         mut, 
-        has_one = admin @ Errors::AdminNotMatch
+        has_one = admin @ Errors::AdminNotMatch,
+        constraint = stake_manager.is_upgraded() @ Errors::StakeManagerNotUpgraded
     )]
     pub stake_manager: Box<Account<'info, StakeManager>>,
 
@@ -120,11 +130,125 @@ impl<'info> SetUnstakeFeeCommission<'info> {
     }
 }
 
+#[derive(Accounts)]
+pub struct SetCancelUnstakeFeeRefund<'info> {
+    #[account(
+        mut, 
+        has_one = admin @ Errors::AdminNotMatch,
+        constraint = stake_manager.is_upgraded() @ Errors::StakeManagerNotUpgraded
+    )]
+    pub stake_manager: Box<Account<'info, StakeManager>>,
+
+    pub admin: Signer<'info>,
+}
+
+impl<'info> SetCancelUnstakeFeeRefund<'info> {
+    // refunds are paid by the fee recipient, which must approve the stake pool as delegate
+    pub fn process(&mut self, fee_refund: bool) -> Result<()> {
+        self.stake_manager.cancel_unstake_fee_refund = fee_refund;
+
+        msg!("SetCancelUnstakeFeeRefund: fee refund: {}", fee_refund);
+        Ok(())
+    }
+}
+
+#[derive(Accounts)]
+pub struct SetReferralFeeShare<'info> {
+    #[account(
+        mut, 
+        has_one = admin @ Errors::AdminNotMatch,
+        constraint = stake_manager.is_upgraded() @ Errors::StakeManagerNotUpgraded
+    )]
+    pub stake_manager: Box<Account<'info, StakeManager>>,
+
+    pub admin: Signer<'info>,
+}
+
+impl<'info> SetReferralFeeShare<'info> {
+    pub fn process(&mut self, referral_fee_share: u64) -> Result<()> {
+        require_gte!(StakeManager::CAL_BASE, referral_fee_share, Errors::InvalidFeeCommission);
+
+        self.stake_manager.referral_fee_share = referral_fee_share;
+
+        msg!("SetReferralFeeShare: referral fee share: {}", referral_fee_share);
+        Ok(())
+    }
+}
+
+#[derive(Accounts)]
+pub struct SetCrankTip<'info> {
+    #[account(
+        mut, 
+        has_one = admin @ Errors::AdminNotMatch,
+        constraint = stake_manager.is_upgraded() @ Errors::StakeManagerNotUpgraded
+    )]
+    pub stake_manager: Box<Account<'info, StakeManager>>,
+
+    pub admin: Signer<'info>,
+}
+
+impl<'info> SetCrankTip<'info> {
+    pub fn process(&mut self, crank_tip: u64, crank_tip_era_budget: u64) -> Result<()> {
+        require_gte!(crank_tip_era_budget, crank_tip, Errors::InvalidCrankTip);
+
+        self.stake_manager.crank_tip = crank_tip;
+        self.stake_manager.crank_tip_era_budget = crank_tip_era_budget;
+
+        msg!("SetCrankTip: tip: {}, era budget: {}", crank_tip, crank_tip_era_budget);
+        Ok(())
+    }
+}
+
+#[derive(Accounts)]
+pub struct SetMaxValidatorCommission<'info> {
+    #[account(
+        mut, 
+        has_one = admin @ Errors::AdminNotMatch,
+        constraint = stake_manager.is_upgraded() @ Errors::StakeManagerNotUpgraded
+    )]
+    pub stake_manager: Box<Account<'info, StakeManager>>,
+
+    pub admin: Signer<'info>,
+}
+
+impl<'info> SetMaxValidatorCommission<'info> {
+    pub fn process(&mut self, max_validator_commission: u8) -> Result<()> {
+        require_gte!(100, max_validator_commission, Errors::InvalidFeeCommission);
+
+        self.stake_manager.max_validator_commission = max_validator_commission;
+
+        msg!("SetMaxValidatorCommission: max validator commission: {}", max_validator_commission);
+        Ok(())
+    }
+}
+
+#[derive(Accounts)]
+pub struct SetMinRebalanceAmount<'info> {
+    #[account(
+        mut, 
+        has_one = admin @ Errors::AdminNotMatch,
+        constraint = stake_manager.is_upgraded() @ Errors::StakeManagerNotUpgraded
+    )]
+    pub stake_manager: Box<Account<'info, StakeManager>>,
+
+    pub admin: Signer<'info>,
+}
+
+impl<'info> SetMinRebalanceAmount<'info> {
+    pub fn process(&mut self, min_rebalance_amount: u64) -> Result<()> {
+        self.stake_manager.min_rebalance_amount = min_rebalance_amount;
+
+        msg!("SetMinRebalanceAmount: min rebalance amount: {}", min_rebalance_amount);
+        Ok(())
+    }
+}
+
 #[derive(Accounts)]
 pub struct SetRateChangeLimit<'info> {
     #[account(
         mut, 
-        has_one = admin @ Errors::AdminNotMatch
+        has_one = admin @ Errors::AdminNotMatch,
+        constraint = stake_manager.is_upgraded() @ Errors::StakeManagerNotUpgraded
     )]
     pub stake_manager: Box<Account<'info, StakeManager>>,
 
@@ -140,21 +264,125 @@ impl<'info> SetRateChangeLimit<'info> {
     }
 }
 
+#[derive(Accounts)]
+pub struct SetReserveTarget<'info> {
+    #[account(
+        mut, 
+        has_one = admin @ Errors::AdminNotMatch,
+        constraint = stake_manager.is_upgraded() @ Errors::StakeManagerNotUpgraded
+    )]
+    pub stake_manager: Box<Account<'info, StakeManager>>,
+
+    #[account(
+        seeds = [
+            &stake_manager.key().to_bytes(),
+            StakeManager::RESERVE_SEED,
+        ],
+        bump,
+    )]
+    pub reserve_pool: SystemAccount<'info>,
+
+    pub admin: Signer<'info>,
+}
+
+impl<'info> SetReserveTarget<'info> {
+    pub fn process(&mut self, reserve_target: u64, reserve_seed_bump: u8) -> Result<()> {
+        require_gte!(
+            self.reserve_pool.lamports(),
+            self.stake_manager.rent_exempt_for_pool_acc,
+            Errors::RentNotEnough
+        );
+
+        self.stake_manager.reserve_target = reserve_target;
+        self.stake_manager.reserve_seed_bump = reserve_seed_bump;
+
+        msg!("SetReserveTarget: reserve target: {}", reserve_target);
+        Ok(())
+    }
+}
+
+#[derive(Accounts)]
+pub struct SetInstantUnstakeFeeCommission<'info> {
+    #[account(
+        mut, 
+        has_one = admin @ Errors::AdminNotMatch,
+        constraint = stake_manager.is_upgraded() @ Errors::StakeManagerNotUpgraded
+    )]
+    pub stake_manager: Box<Account<'info, StakeManager>>,
+
+    pub admin: Signer<'info>,
+}
+
+impl<'info> SetInstantUnstakeFeeCommission<'info> {
+    pub fn process(&mut self, min_fee_commission: u64, max_fee_commission: u64) -> Result<()> {
+        require_gte!(max_fee_commission, min_fee_commission, Errors::InvalidFeeCommission);
+        require_gte!(StakeManager::CAL_BASE, max_fee_commission, Errors::InvalidFeeCommission);
+
+        self.stake_manager.instant_unstake_min_fee_commission = min_fee_commission;
+        self.stake_manager.instant_unstake_max_fee_commission = max_fee_commission;
+
+        msg!(
+            "SetInstantUnstakeFeeCommission: min: {}, max: {}",
+            min_fee_commission,
+            max_fee_commission
+        );
+        Ok(())
+    }
+}
+
 #[derive(Accounts)]
 pub struct AddValidator<'info> {
     #[account(
         mut, 
-        has_one = admin @ Errors::AdminNotMatch
+        has_one = admin @ Errors::AdminNotMatch,
+        constraint = stake_manager.is_upgraded() @ Errors::StakeManagerNotUpgraded
     )]
     pub stake_manager: Box<Account<'info, StakeManager>>,
 
+    #[account(
+        mut,
+        address = stake_manager.validator_registry @ Errors::ValidatorRegistryNotMatch
+    )]
+    pub validator_registry: Box<Account<'info, ValidatorRegistry>>,
+
+    /// CHECK: vote account, checked on process func
+    pub validator: UncheckedAccount<'info>,
+
     pub admin: Signer<'info>,
+
+    pub clock: Sysvar<'info, Clock>,
 }
 
 impl<'info> AddValidator<'info> {
-    pub fn process(&mut self, new_validator: Pubkey) -> Result<()> {
+    pub fn process(&mut self) -> Result<()> {
+        let new_validator = self.validator.key();
+
+        let vote_state = VoteAccountState::parse(&self.validator)?;
+        vote_state.check_commission(self.stake_manager.max_validator_commission)?;
+        vote_state.check_recent_credits(self.clock.epoch)?;
+
         require!(!self.stake_manager.validators.contains(&new_validator), Errors::ValidatorAlreadyExist);
 
+        match self
+            .validator_registry
+            .validators
+            .iter_mut()
+            .find(|e| e.vote_account == new_validator)
+        {
+            Some(entry) => entry.status = ValidatorStatus::Active,
+            None => {
+                require_gt!(
+                    MAX_VALIDATORS,
+                    self.validator_registry.validators.len(),
+                    Errors::ValidatorRegistryFull
+                );
+                self.validator_registry.validators.push(ValidatorEntry {
+                    vote_account: new_validator,
+                    ..ValidatorEntry::default()
+                });
+            }
+        }
+
         self.stake_manager.validators.push(new_validator);
 
         msg!("AddValidator: new validator: {}", new_validator.key().to_string());
@@ -166,10 +394,17 @@ impl<'info> AddValidator<'info> {
 pub struct RemoveValidator<'info> {
     #[account(
         mut, 
-        has_one = admin @ Errors::AdminNotMatch
+        has_one = admin @ Errors::AdminNotMatch,
+        constraint = stake_manager.is_upgraded() @ Errors::StakeManagerNotUpgraded
     )]
     pub stake_manager: Box<Account<'info, StakeManager>>,
 
+    #[account(
+        mut,
+        address = stake_manager.validator_registry @ Errors::ValidatorRegistryNotMatch
+    )]
+    pub validator_registry: Box<Account<'info, ValidatorRegistry>>,
+
     pub admin: Signer<'info>,
 }
 
@@ -179,11 +414,151 @@ impl<'info> RemoveValidator<'info> {
 
         self.stake_manager.validators.retain(|&e| e != remove_validator);
 
+        // stake still delegated to it is drained by the next eras' unbonds before it is removed
+        let entry = self.validator_registry.get_mut(&remove_validator)?;
+        entry.status = if entry.stake_accounts_len > 0 {
+            ValidatorStatus::Draining
+        } else {
+            ValidatorStatus::Removed
+        };
+        entry.weight = 0;
+
         msg!("RemoveValidator: remove validator: {}", remove_validator.key().to_string());
         Ok(())
     }
 }
 
+#[derive(Accounts)]
+pub struct SetValidatorWeight<'info> {
+    #[account(
+        mut, 
+        has_one = admin @ Errors::AdminNotMatch,
+        constraint = stake_manager.is_upgraded() @ Errors::StakeManagerNotUpgraded
+    )]
+    pub stake_manager: Box<Account<'info, StakeManager>>,
+
+    #[account(
+        mut,
+        address = stake_manager.validator_registry @ Errors::ValidatorRegistryNotMatch
+    )]
+    pub validator_registry: Box<Account<'info, ValidatorRegistry>>,
+
+    pub admin: Signer<'info>,
+}
+
+impl<'info> SetValidatorWeight<'info> {
+    pub fn process(&mut self, validator: Pubkey, weight: u64) -> Result<()> {
+        require!(self.stake_manager.validators.contains(&validator), Errors::ValidatorNotExist);
+
+        self.validator_registry.get_mut(&validator)?.weight = weight;
+
+        msg!("SetValidatorWeight: validator: {}, weight: {}", validator, weight);
+        Ok(())
+    }
+}
+
+#[derive(Accounts)]
+pub struct InitializeValidatorRegistry<'info> {
+    #[account(
+        mut, 
+        has_one = admin @ Errors::AdminNotMatch,
+        constraint = stake_manager.is_upgraded() @ Errors::StakeManagerNotUpgraded
+    )]
+    pub stake_manager: Box<Account<'info, StakeManager>>,
+
+    #[account(
+        init,
+        payer = rent_payer,
+        space = 8 + ValidatorRegistry::INIT_SPACE,
+        seeds = [
+            &stake_manager.key().to_bytes(),
+            ValidatorRegistry::SEED,
+        ],
+        bump,
+    )]
+    pub validator_registry: Box<Account<'info, ValidatorRegistry>>,
+
+    pub admin: Signer<'info>,
+
+    #[account(
+        mut,
+        owner = system_program::ID,
+    )]
+    pub rent_payer: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> InitializeValidatorRegistry<'info> {
+    pub fn process(&mut self) -> Result<()> {
+        require_gte!(
+            MAX_VALIDATORS,
+            self.stake_manager.validators.len(),
+            Errors::ValidatorRegistryFull
+        );
+
+        // existing validators start with equal weights until the admin sets them,
+        // stakes are filled in by the next era update active
+        self.validator_registry.stake_manager = self.stake_manager.key();
+        self.validator_registry.validators = self
+            .stake_manager
+            .validators
+            .iter()
+            .map(|&vote_account| ValidatorEntry {
+                vote_account,
+                weight: ValidatorRegistry::DEFAULT_WEIGHT,
+                ..ValidatorEntry::default()
+            })
+            .collect();
+        self.stake_manager.validator_registry = self.validator_registry.key();
+
+        msg!("InitializeValidatorRegistry: validator registry: {}", self.validator_registry.key());
+        Ok(())
+    }
+}
+
+#[derive(Accounts)]
+pub struct InitializeEraHistory<'info> {
+    #[account(
+        mut, 
+        has_one = admin @ Errors::AdminNotMatch,
+        constraint = stake_manager.is_upgraded() @ Errors::StakeManagerNotUpgraded
+    )]
+    pub stake_manager: Box<Account<'info, StakeManager>>,
+
+    #[account(
+        init,
+        payer = rent_payer,
+        space = 8 + EraHistory::INIT_SPACE,
+        seeds = [
+            &stake_manager.key().to_bytes(),
+            EraHistory::SEED,
+        ],
+        bump,
+    )]
+    pub era_history: Box<Account<'info, EraHistory>>,
+
+    pub admin: Signer<'info>,
+
+    #[account(
+        mut,
+        owner = system_program::ID,
+    )]
+    pub rent_payer: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> InitializeEraHistory<'info> {
+    pub fn process(&mut self) -> Result<()> {
+        self.era_history.stake_manager = self.stake_manager.key();
+        self.stake_manager.era_history = self.era_history.key();
+
+        msg!("InitializeEraHistory: era history: {}", self.era_history.key());
+        Ok(())
+    }
+}
+
 #[derive(Accounts)]
 #[instruction(new_size: u32)]
 pub struct ReallocStakeManager<'info> {
@@ -193,6 +568,7 @@ pub struct ReallocStakeManager<'info> {
         realloc = new_size as usize,
         realloc::payer = rent_payer,
         realloc::zero = false,
+        constraint = stake_manager.is_upgraded() @ Errors::StakeManagerNotUpgraded
     )]
     pub stake_manager: Box<Account<'info, StakeManager>>,
 
@@ -217,12 +593,231 @@ impl<'info> ReallocStakeManager<'info> {
 #[derive(Accounts)]
 pub struct UpgradeStakeManager<'info> {
     /// CHECK: on process func 
+    #[account(mut)]
     pub stake_manager: AccountInfo<'info>,
     pub admin: Signer<'info>,   
+
+    #[account(
+        mut,
+        owner = system_program::ID,
+    )]
+    pub rent_payer: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
 }
 
 impl<'info> UpgradeStakeManager<'info> {
+    // rewrite a legacy stake manager into the current layout, every new field starts from an explicit default,
+    // except the sum of the open legacy tickets, which is counted off chain so their lamports are not taken as pool excess
+    pub fn process(&mut self, total_unstake_unclaimed: u64) -> Result<()> {
+        require_keys_eq!(
+            *self.stake_manager.owner,
+            crate::ID,
+            anchor_lang::error::ErrorCode::AccountOwnedByWrongProgram
+        );
+
+        let (legacy, legacy_len) = {
+            let data = self.stake_manager.try_borrow_data()?;
+            require!(
+                data.len() >= 8 && data[..8] == <StakeManager as Discriminator>::DISCRIMINATOR,
+                anchor_lang::error::ErrorCode::AccountDiscriminatorMismatch
+            );
+
+            if let Ok(stake_manager) = StakeManager::try_deserialize(&mut &data[..]) {
+                require!(
+                    !stake_manager.is_upgraded(),
+                    Errors::StakeManagerAlreadyUpgraded
+                );
+            }
+
+            let mut legacy_data: &[u8] = &data[8..];
+            let legacy = LegacyStakeManager::deserialize(&mut legacy_data)
+                .map_err(|_| error!(anchor_lang::error::ErrorCode::AccountDidNotDeserialize))?;
+            (legacy, data.len() - 8 - legacy_data.len())
+        };
+
+        require_keys_eq!(legacy.admin, self.admin.key(), Errors::AdminNotMatch);
+
+        // an era left half processed resumes from the phase its remaining work implies
+        let need_bond = legacy.era_process_data.need_bond;
+        let era_process_data = EraProcessData {
+            need_bond,
+            need_unbond: legacy.era_process_data.need_unbond,
+            old_active: legacy.era_process_data.old_active,
+            new_active: legacy.era_process_data.new_active,
+            pending_stake_accounts: legacy.era_process_data.pending_stake_accounts,
+            bonded_validators: vec![],
+            skipped_validators: vec![],
+            bridged_epochs: 1,
+        };
+        let era_phase = era_process_data.next_phase();
+
+        let stake_manager = StakeManager {
+            admin: legacy.admin,
+            balancer: legacy.balancer,
+            rsol_mint: legacy.rsol_mint,
+            fee_recipient: legacy.fee_recipient,
+            pool_seed_bump: legacy.pool_seed_bump,
+            rent_exempt_for_pool_acc: legacy.rent_exempt_for_pool_acc,
+            min_stake_amount: legacy.min_stake_amount,
+            unstake_fee_commission: legacy.unstake_fee_commission,
+            protocol_fee_commission: legacy.protocol_fee_commission,
+            rate_change_limit: legacy.rate_change_limit,
+            stake_accounts_len_limit: legacy.stake_accounts_len_limit,
+            split_accounts_len_limit: legacy.split_accounts_len_limit,
+            unbonding_duration: legacy.unbonding_duration,
+            latest_era: legacy.latest_era,
+            rate: legacy.rate,
+            era_bond: legacy.era_bond,
+            era_unbond: legacy.era_unbond,
+            active: legacy.active,
+            total_rsol_supply: legacy.total_rsol_supply,
+            total_protocol_fee: legacy.total_protocol_fee,
+            validators: legacy.validators,
+            stake_accounts: legacy.stake_accounts,
+            split_accounts: legacy.split_accounts,
+            era_process_data,
+            reserve_seed_bump: 0,
+            reserve_target: 0,
+            reserve_balance: 0,
+            reserve_fee_collected: 0,
+            era_reserve_fill: 0,
+            era_reserve_release: 0,
+            instant_unstake_min_fee_commission:
+                StakeManager::DEFAULT_INSTANT_UNSTAKE_MIN_FEE_COMMISSION,
+            instant_unstake_max_fee_commission:
+                StakeManager::DEFAULT_INSTANT_UNSTAKE_MAX_FEE_COMMISSION,
+            cancel_unstake_fee_refund: false,
+            referral_fee_share: 0,
+            total_referred_rsol: 0,
+            referral_reward_per_rsol: 0,
+            crank_tip: 0,
+            crank_tip_era_budget: 0,
+            crank_tip_era_paid: 0,
+            crank_tip_unsettled: 0,
+            crank_tip_fund: 0,
+            total_crank_tip_paid: 0,
+            era_phase,
+            total_unstake_unclaimed,
+            last_rate_override_era: 0,
+            era_history: Pubkey::default(),
+            validator_registry: Pubkey::default(),
+            max_validator_commission: StakeManager::DEFAULT_MAX_VALIDATOR_COMMISSION,
+            delinquent_stake: 0,
+            min_rebalance_amount: StakeManager::DEFAULT_MIN_REBALANCE_AMOUNT,
+            version: StakeManager::VERSION,
+        };
+        let stake_manager_data = stake_manager.try_to_vec()?;
+
+        // keep the spare room the legacy account had for its vectors
+        let new_len = self.stake_manager.data_len() - legacy_len + stake_manager_data.len();
+        let rent_lamports = Rent::get()?
+            .minimum_balance(new_len)
+            .saturating_sub(self.stake_manager.lamports());
+        if rent_lamports > 0 {
+            system_program::transfer(
+                CpiContext::new(
+                    self.system_program.to_account_info(),
+                    system_program::Transfer {
+                        from: self.rent_payer.to_account_info(),
+                        to: self.stake_manager.to_account_info(),
+                    },
+                ),
+                rent_lamports,
+            )?;
+        }
+        self.stake_manager.realloc(new_len, false)?;
+
+        let mut data = self.stake_manager.try_borrow_mut_data()?;
+        data[8..8 + stake_manager_data.len()].copy_from_slice(&stake_manager_data);
+        data[8 + stake_manager_data.len()..].fill(0);
+
+        msg!(
+            "UpgradeStakeManager: new_size: {}, total_unstake_unclaimed: {}",
+            new_len,
+            total_unstake_unclaimed
+        );
+        Ok(())
+    }
+}
+
+#[derive(Accounts)]
+pub struct UpgradeUnstakeAccount<'info> {
+    #[account(constraint = stake_manager.is_upgraded() @ Errors::StakeManagerNotUpgraded)]
+    pub stake_manager: Box<Account<'info, StakeManager>>,
+
+    /// CHECK: on process func
+    #[account(mut)]
+    pub unstake_account: AccountInfo<'info>,
+
+    #[account(
+        mut,
+        owner = system_program::ID,
+    )]
+    pub rent_payer: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> UpgradeUnstakeAccount<'info> {
+    // permissionless, a legacy ticket only gains the new fields and stays claimable by its recipient
     pub fn process(&mut self) -> Result<()> {
+        require_keys_eq!(
+            *self.unstake_account.owner,
+            crate::ID,
+            anchor_lang::error::ErrorCode::AccountOwnedByWrongProgram
+        );
+
+        let legacy = {
+            let data = self.unstake_account.try_borrow_data()?;
+            require!(
+                data.len() == 8 + LegacyUnstakeAccount::LEN
+                    && data[..8] == <UnstakeAccount as Discriminator>::DISCRIMINATOR,
+                Errors::InvalidUnstakeAccount
+            );
+
+            LegacyUnstakeAccount::deserialize(&mut &data[8..])
+                .map_err(|_| error!(anchor_lang::error::ErrorCode::AccountDidNotDeserialize))?
+        };
+
+        require_keys_eq!(
+            legacy.stake_manager,
+            self.stake_manager.key(),
+            Errors::InvalidUnstakeAccount
+        );
+
+        // legacy tickets were netted in an era long gone, so they are never cancelable
+        let unstake_account = UnstakeAccount {
+            stake_manager: legacy.stake_manager,
+            recipient: legacy.recipient,
+            amount: legacy.amount,
+            created_epoch: legacy.created_epoch,
+            created_era: 0,
+            unstake_fee: 0,
+            pending_recipient: Pubkey::default(),
+        };
+
+        let new_len = 8 + UnstakeAccount::INIT_SPACE;
+        let rent_lamports = Rent::get()?
+            .minimum_balance(new_len)
+            .saturating_sub(self.unstake_account.lamports());
+        if rent_lamports > 0 {
+            system_program::transfer(
+                CpiContext::new(
+                    self.system_program.to_account_info(),
+                    system_program::Transfer {
+                        from: self.rent_payer.to_account_info(),
+                        to: self.unstake_account.to_account_info(),
+                    },
+                ),
+                rent_lamports,
+            )?;
+        }
+        self.unstake_account.realloc(new_len, false)?;
+
+        let mut data = self.unstake_account.try_borrow_mut_data()?;
+        unstake_account.try_serialize(&mut &mut data[..])?;
+
         Ok(())
     }
 }
@@ -0,0 +1,48 @@
+use crate::StakeManager;
+use anchor_lang::prelude::*;
+use anchor_lang::system_program::{transfer, Transfer};
+
+#[event]
+pub struct EventCrankTip {
+    pub era: u64,
+    pub cranker: Pubkey,
+    pub tip: u64,
+}
+
+// pay the admin-set tip from the pool to the caller of an era step
+pub fn pay_crank_tip<'info>(
+    stake_manager: &mut Account<'info, StakeManager>,
+    stake_pool: &SystemAccount<'info>,
+    cranker: AccountInfo<'info>,
+    system_program: &Program<'info, System>,
+) -> Result<()> {
+    let tip = stake_manager.calc_crank_tip(stake_pool.lamports());
+    if tip == 0 {
+        return Ok(());
+    }
+
+    transfer(
+        CpiContext::new_with_signer(
+            system_program.to_account_info(),
+            Transfer {
+                from: stake_pool.to_account_info(),
+                to: cranker.clone(),
+            },
+            &[&[
+                &stake_manager.key().to_bytes(),
+                StakeManager::POOL_SEED,
+                &[stake_manager.pool_seed_bump],
+            ]],
+        ),
+        tip,
+    )?;
+
+    stake_manager.record_crank_tip(tip);
+
+    emit!(EventCrankTip {
+        era: stake_manager.latest_era,
+        cranker: cranker.key(),
+        tip
+    });
+    Ok(())
+}
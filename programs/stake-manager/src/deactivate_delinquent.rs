@@ -0,0 +1,192 @@
+use crate::{EraPhase, Errors, StakeManager, ValidatorRegistry, ValidatorStatus, VoteAccountState};
+use anchor_lang::prelude::*;
+use anchor_lang::solana_program::{
+    program::invoke,
+    stake::{self, state::Delegation},
+    vote,
+};
+use anchor_spl::stake::{Stake, StakeAccount};
+
+#[derive(Accounts)]
+pub struct DeactivateDelinquent<'info> {
+    #[account(
+        mut,
+        constraint = stake_manager.is_upgraded() @ Errors::StakeManagerNotUpgraded
+    )]
+    pub stake_manager: Box<Account<'info, StakeManager>>,
+
+    #[account(
+        mut,
+        address = stake_manager.validator_registry @ Errors::ValidatorRegistryNotMatch
+    )]
+    pub validator_registry: Box<Account<'info, ValidatorRegistry>>,
+
+    #[account(mut)]
+    pub stake_account: Account<'info, StakeAccount>,
+
+    /// CHECK: delinquency is checked by the stake program
+    #[account(owner = vote::program::ID @ Errors::InvalidVoteAccount)]
+    pub delinquent_vote_account: UncheckedAccount<'info>,
+
+    /// CHECK: recent votes are checked by the stake program
+    #[account(owner = vote::program::ID @ Errors::InvalidVoteAccount)]
+    pub reference_vote_account: UncheckedAccount<'info>,
+
+    pub stake_program: Program<'info, Stake>,
+}
+
+#[event]
+pub struct EventDeactivateDelinquent {
+    pub era: u64,
+    pub validator: Pubkey,
+    pub stake_account: Pubkey,
+    pub stake_amount: u64,
+}
+
+// the only way a pool stake account deactivates without the pool is the stake program's
+// delinquent deactivation, so such an account is moved to split accounts with its stake
+// still counted in active until era_withdraw returns it to the pool for restaking
+pub fn record_delinquent_stake(
+    stake_manager: &mut StakeManager,
+    validator_registry: &mut ValidatorRegistry,
+    stake_account: &Pubkey,
+    delegation: &Delegation,
+) -> Result<()> {
+    stake_manager.stake_accounts.retain(|e| e != stake_account);
+    stake_manager
+        .era_process_data
+        .pending_stake_accounts
+        .retain(|e| e != stake_account);
+    stake_manager.split_accounts.push(*stake_account);
+    stake_manager.delinquent_stake += delegation.stake;
+
+    validator_registry.add_deactivating_stake(&delegation.voter_pubkey, delegation.stake)?;
+    validator_registry.remove_stake_account(&delegation.voter_pubkey)?;
+    // a draining or removed validator keeps its status so it can still be fully removed
+    let entry = validator_registry.get_mut(&delegation.voter_pubkey)?;
+    if entry.status == ValidatorStatus::Active {
+        entry.status = ValidatorStatus::Delinquent;
+    }
+    Ok(())
+}
+
+impl<'info> DeactivateDelinquent<'info> {
+    pub fn process(&mut self) -> Result<()> {
+        require!(
+            self.stake_manager.era_phase == EraPhase::Idle,
+            Errors::EraIsProcessing
+        );
+
+        require!(
+            self.stake_manager
+                .stake_accounts
+                .contains(&self.stake_account.key()),
+            Errors::StakeAccountNotExist
+        );
+
+        let delegation = self
+            .stake_account
+            .delegation()
+            .ok_or_else(|| error!(Errors::DelegationEmpty))?;
+
+        require_keys_eq!(
+            delegation.voter_pubkey,
+            self.delinquent_vote_account.key(),
+            Errors::ValidatorNotMatch
+        );
+
+        require_gt!(
+            self.stake_manager.split_accounts_len_limit,
+            self.stake_manager.split_accounts.len() as u64,
+            Errors::SplitAccountsLenOverLimit
+        );
+
+        // the stake program instruction is permissionless, anyone may have deactivated it already
+        if delegation.deactivation_epoch == u64::MAX {
+            invoke(
+                &stake::instruction::deactivate_delinquent_stake(
+                    &self.stake_account.key(),
+                    &self.delinquent_vote_account.key(),
+                    &self.reference_vote_account.key(),
+                ),
+                &[
+                    self.stake_program.to_account_info(),
+                    self.stake_account.to_account_info(),
+                    self.delinquent_vote_account.to_account_info(),
+                    self.reference_vote_account.to_account_info(),
+                ],
+            )?;
+        }
+
+        record_delinquent_stake(
+            &mut self.stake_manager,
+            &mut self.validator_registry,
+            &self.stake_account.key(),
+            &delegation,
+        )?;
+
+        emit!(EventDeactivateDelinquent {
+            era: self.stake_manager.latest_era,
+            validator: delegation.voter_pubkey,
+            stake_account: self.stake_account.key(),
+            stake_amount: delegation.stake
+        });
+        Ok(())
+    }
+}
+
+#[derive(Accounts)]
+pub struct ReactivateDelinquent<'info> {
+    #[account(constraint = stake_manager.is_upgraded() @ Errors::StakeManagerNotUpgraded)]
+    pub stake_manager: Box<Account<'info, StakeManager>>,
+
+    #[account(
+        mut,
+        address = stake_manager.validator_registry @ Errors::ValidatorRegistryNotMatch
+    )]
+    pub validator_registry: Box<Account<'info, ValidatorRegistry>>,
+
+    /// CHECK: validator account
+    pub validator: UncheckedAccount<'info>,
+
+    pub clock: Sysvar<'info, Clock>,
+}
+
+#[event]
+pub struct EventReactivateDelinquent {
+    pub era: u64,
+    pub validator: Pubkey,
+}
+
+impl<'info> ReactivateDelinquent<'info> {
+    // permissionless, a delinquent validator that votes again takes its weight back
+    pub fn process(&mut self) -> Result<()> {
+        require!(
+            self.stake_manager.era_phase == EraPhase::Idle,
+            Errors::EraIsProcessing
+        );
+
+        require!(
+            self.stake_manager.validators.contains(self.validator.key),
+            Errors::ValidatorNotExist
+        );
+
+        let entry = self.validator_registry.get_mut(self.validator.key)?;
+        require!(
+            entry.status == ValidatorStatus::Delinquent,
+            Errors::ValidatorNotDelinquent
+        );
+
+        let vote_state = VoteAccountState::parse(&self.validator)?;
+        vote_state.check_commission(self.stake_manager.max_validator_commission)?;
+        vote_state.check_recent_credits(self.clock.epoch)?;
+
+        entry.status = ValidatorStatus::Active;
+
+        emit!(EventReactivateDelinquent {
+            era: self.stake_manager.latest_era,
+            validator: self.validator.key()
+        });
+        Ok(())
+    }
+}
@@ -1,4 +1,7 @@
-use crate::{Errors, StakeManager};
+use crate::{
+    pay_crank_tip, EraPhase, Errors, StakeManager, ValidatorRegistry, ValidatorStatus,
+    VoteAccountState,
+};
 use anchor_lang::prelude::*;
 use anchor_lang::system_program::{transfer, Transfer};
 use anchor_lang::{
@@ -16,9 +19,18 @@ use anchor_spl::stake::{Stake, StakeAccount};
 
 #[derive(Accounts)]
 pub struct EraBond<'info> {
-    #[account(mut)]
+    #[account(
+        mut,
+        constraint = stake_manager.is_upgraded() @ Errors::StakeManagerNotUpgraded
+    )]
     pub stake_manager: Box<Account<'info, StakeManager>>,
 
+    #[account(
+        mut,
+        address = stake_manager.validator_registry @ Errors::ValidatorRegistryNotMatch
+    )]
+    pub validator_registry: Box<Account<'info, ValidatorRegistry>>,
+
     /// CHECK: validator account
     #[account(mut)]
     pub validator: UncheckedAccount<'info>,
@@ -69,7 +81,7 @@ pub struct EventEraBond {
 impl<'info> EraBond<'info> {
     pub fn process(&mut self) -> Result<()> {
         require!(
-            self.stake_manager.era_process_data.need_bond(),
+            self.stake_manager.era_phase == EraPhase::Bond,
             Errors::EraNoNeedBond
         );
 
@@ -86,7 +98,29 @@ impl<'info> EraBond<'info> {
             Errors::StakeAccountAlreadyExist
         );
 
-        let need_bond = self.stake_manager.era_process_data.need_bond;
+        // every bond adds a stake account, era_merge folds them back into one per validator
+        require_gt!(
+            self.stake_manager.stake_accounts_len_limit,
+            self.stake_manager.stake_accounts.len() as u64,
+            Errors::StakeAccountsLenOverLimit
+        );
+
+        require!(
+            self.validator_registry
+                .get(self.validator.key)
+                .map(|e| e.status)
+                != Some(ValidatorStatus::Delinquent),
+            Errors::ValidatorDelinquent
+        );
+
+        VoteAccountState::parse(&self.validator)?
+            .check_commission(self.stake_manager.max_validator_commission)?;
+
+        let bond_amount = self
+            .validator_registry
+            .calc_validator_bond(&self.stake_manager.era_process_data, self.validator.key)?;
+        require_gt!(bond_amount, 0, Errors::ValidatorBondOverTarget);
+
         transfer(
             CpiContext::new_with_signer(
                 self.system_program.to_account_info(),
@@ -100,7 +134,7 @@ impl<'info> EraBond<'info> {
                     &[self.stake_manager.pool_seed_bump],
                 ]],
             ),
-            need_bond,
+            bond_amount,
         )?;
 
         invoke(
@@ -141,7 +175,20 @@ impl<'info> EraBond<'info> {
             ]],
         )?;
 
-        self.stake_manager.era_process_data.need_bond = 0;
+        self.validator_registry
+            .add_activating_stake(self.validator.key, bond_amount)?;
+        self.validator_registry
+            .add_stake_account(self.validator.key)?;
+
+        self.stake_manager.era_process_data.need_bond -= bond_amount;
+        self.stake_manager
+            .era_process_data
+            .bonded_validators
+            .push(self.validator.key());
+        if self.stake_manager.era_process_data.need_bond == 0 {
+            self.stake_manager.era_process_data.clear_bond_progress();
+        }
+
         self.stake_manager
             .stake_accounts
             .push(self.stake_account.key());
@@ -151,10 +198,19 @@ impl<'info> EraBond<'info> {
             .pending_stake_accounts
             .push(self.stake_account.key());
 
+        self.stake_manager.advance_era_phase()?;
+
+        pay_crank_tip(
+            &mut self.stake_manager,
+            &self.stake_pool,
+            self.rent_payer.to_account_info(),
+            &self.system_program,
+        )?;
+
         emit!(EventEraBond {
             era: self.stake_manager.latest_era,
             stake_account: self.stake_account.key(),
-            bond_amount: need_bond
+            bond_amount
         });
         Ok(())
     }
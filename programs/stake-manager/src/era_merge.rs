@@ -1,4 +1,4 @@
-use crate::{Errors, StakeManager};
+use crate::{pay_crank_tip, EraPhase, Errors, StakeManager, ValidatorRegistry};
 use anchor_lang::prelude::*;
 use anchor_lang::solana_program::sysvar::stake_history;
 use anchor_lang::solana_program::{program::invoke_signed, stake};
@@ -6,9 +6,18 @@ use anchor_spl::stake::{Stake, StakeAccount};
 
 #[derive(Accounts)]
 pub struct EraMerge<'info> {
-    #[account(mut)]
+    #[account(
+        mut,
+        constraint = stake_manager.is_upgraded() @ Errors::StakeManagerNotUpgraded
+    )]
     pub stake_manager: Box<Account<'info, StakeManager>>,
 
+    #[account(
+        mut,
+        address = stake_manager.validator_registry @ Errors::ValidatorRegistryNotMatch
+    )]
+    pub validator_registry: Box<Account<'info, ValidatorRegistry>>,
+
     #[account(mut)]
     pub src_stake_account: Box<Account<'info, StakeAccount>>,
 
@@ -16,6 +25,7 @@ pub struct EraMerge<'info> {
     pub dst_stake_account: Box<Account<'info, StakeAccount>>,
 
     #[account(
+        mut,
         seeds = [
             &stake_manager.key().to_bytes(),
             StakeManager::POOL_SEED
@@ -24,11 +34,15 @@ pub struct EraMerge<'info> {
     )]
     pub stake_pool: SystemAccount<'info>,
 
+    #[account(mut)]
+    pub cranker: Signer<'info>,
+
     pub clock: Sysvar<'info, Clock>,
     /// CHECK: stake history
     #[account(address = stake_history::ID)]
     pub stake_history: UncheckedAccount<'info>,
     pub stake_program: Program<'info, Stake>,
+    pub system_program: Program<'info, System>,
 }
 
 #[event]
@@ -40,7 +54,7 @@ pub struct EventEraMerge {
 impl<'info> EraMerge<'info> {
     pub fn process(&mut self) -> Result<()> {
         require!(
-            self.stake_manager.era_process_data.is_empty(),
+            self.stake_manager.era_phase == EraPhase::Idle,
             Errors::EraIsProcessing
         );
 
@@ -110,6 +124,16 @@ impl<'info> EraMerge<'info> {
         self.stake_manager
             .stake_accounts
             .retain(|&e| e != self.src_stake_account.key());
+        // the merged stake stays on the same validator, only its account count drops
+        self.validator_registry
+            .remove_stake_account(&src_delegation.voter_pubkey)?;
+
+        pay_crank_tip(
+            &mut self.stake_manager,
+            &self.stake_pool,
+            self.cranker.to_account_info(),
+            &self.system_program,
+        )?;
 
         emit!(EventEraMerge {
             src_stake_account: self.src_stake_account.key(),
@@ -1,12 +1,35 @@
-use crate::{EraProcessData, Errors, StakeManager};
+use crate::{pay_crank_tip, EraPhase, EraProcessData, Errors, StakeManager, ValidatorRegistry};
 use anchor_lang::prelude::*;
 
 #[derive(Accounts)]
 pub struct EraNew<'info> {
-    #[account(mut)]
+    #[account(
+        mut,
+        constraint = stake_manager.is_upgraded() @ Errors::StakeManagerNotUpgraded
+    )]
     pub stake_manager: Box<Account<'info, StakeManager>>,
 
+    #[account(
+        mut,
+        address = stake_manager.validator_registry @ Errors::ValidatorRegistryNotMatch
+    )]
+    pub validator_registry: Box<Account<'info, ValidatorRegistry>>,
+
+    #[account(
+        mut,
+        seeds = [
+            &stake_manager.key().to_bytes(),
+            StakeManager::POOL_SEED
+        ],
+        bump = stake_manager.pool_seed_bump
+    )]
+    pub stake_pool: SystemAccount<'info>,
+
+    #[account(mut)]
+    pub cranker: Signer<'info>,
+
     pub clock: Sysvar<'info, Clock>,
+    pub system_program: Program<'info, System>,
 }
 
 #[event]
@@ -15,48 +38,72 @@ pub struct EventEraNew {
     pub need_bond: u64,
     pub need_unbond: u64,
     pub active: u64,
+    pub bridged_epochs: u64,
 }
 
 impl<'info> EraNew<'info> {
-    pub fn process(&mut self) -> Result<()> {
-        let new_era = self.stake_manager.latest_era + 1;
+    // catch up jumps to the current epoch when the crank has skipped epochs
+    pub fn process(&mut self, catch_up: bool) -> Result<()> {
+        let next_era = self.stake_manager.latest_era + 1;
 
-        require_gte!(self.clock.epoch, new_era, Errors::EraIsLatest);
+        require_gte!(self.clock.epoch, next_era, Errors::EraIsLatest);
         require!(
-            self.stake_manager.era_process_data.is_empty(),
+            self.stake_manager.era_phase == EraPhase::Idle,
             Errors::EraIsProcessing
         );
 
-        let (need_bond, need_unbond) =
-            if self.stake_manager.era_bond > self.stake_manager.era_unbond {
-                (
-                    self.stake_manager.era_bond - self.stake_manager.era_unbond,
-                    0,
-                )
-            } else {
-                (
-                    0,
-                    self.stake_manager.era_unbond - self.stake_manager.era_bond,
-                )
-            };
+        self.stake_manager.fund_crank_tips();
+
+        // reserve releases are bonded and reserve fills are taken out of the bond like unbonds
+        let era_bond = self.stake_manager.era_bond + self.stake_manager.era_reserve_release;
+        let era_unbond = self.stake_manager.era_unbond + self.stake_manager.era_reserve_fill;
+        let (need_bond, need_unbond) = if era_bond > era_unbond {
+            (era_bond - era_unbond, 0)
+        } else {
+            (0, era_unbond - era_bond)
+        };
+
+        let new_era = if catch_up { self.clock.epoch } else { next_era };
+        let bridged_epochs = new_era - self.stake_manager.latest_era;
 
         self.stake_manager.latest_era = new_era;
         self.stake_manager.era_bond = 0;
         self.stake_manager.era_unbond = 0;
+        self.stake_manager.era_reserve_fill = 0;
+        self.stake_manager.era_reserve_release = 0;
+        self.stake_manager.crank_tip_era_paid = 0;
+        self.validator_registry.clear_transient_stake();
 
         self.stake_manager.era_process_data = EraProcessData {
             need_bond,
             need_unbond,
-            old_active: self.stake_manager.active,
+            // the reserve is held outside stake accounts and never shows up in new_active
+            old_active: self
+                .stake_manager
+                .active
+                .saturating_sub(self.stake_manager.reserve_balance),
             new_active: 0,
             pending_stake_accounts: self.stake_manager.stake_accounts.clone(),
+            bonded_validators: vec![],
+            skipped_validators: vec![],
+            bridged_epochs,
         };
 
+        self.stake_manager.advance_era_phase()?;
+
+        pay_crank_tip(
+            &mut self.stake_manager,
+            &self.stake_pool,
+            self.cranker.to_account_info(),
+            &self.system_program,
+        )?;
+
         emit!(EventEraNew {
             new_era,
             need_bond,
             need_unbond,
-            active: self.stake_manager.active
+            active: self.stake_manager.active,
+            bridged_epochs
         });
         Ok(())
     }
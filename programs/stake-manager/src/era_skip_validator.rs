@@ -0,0 +1,90 @@
+use crate::{EraPhase, Errors, StakeManager, ValidatorRegistry, VoteAccountState};
+use anchor_lang::prelude::*;
+
+#[derive(Accounts)]
+pub struct EraSkipValidator<'info> {
+    #[account(
+        mut,
+        constraint = stake_manager.is_upgraded() @ Errors::StakeManagerNotUpgraded
+    )]
+    pub stake_manager: Box<Account<'info, StakeManager>>,
+
+    #[account(address = stake_manager.validator_registry @ Errors::ValidatorRegistryNotMatch)]
+    pub validator_registry: Box<Account<'info, ValidatorRegistry>>,
+
+    /// CHECK: validator account
+    pub validator: UncheckedAccount<'info>,
+}
+
+#[event]
+pub struct EventEraSkipValidator {
+    pub era: u64,
+    pub validator: Pubkey,
+    pub need_bond: u64,
+    pub rolled_over: u64,
+}
+
+impl<'info> EraSkipValidator<'info> {
+    // permissionless, a weighted validator whose vote account fails the bond checks, or any
+    // once the pool is out of stake account room, is skipped for this era instead of blocking
+    // the bond phase
+    pub fn process(&mut self) -> Result<()> {
+        require!(
+            self.stake_manager.era_phase == EraPhase::Bond,
+            Errors::EraNoNeedBond
+        );
+
+        require!(
+            self.stake_manager.validators.contains(self.validator.key),
+            Errors::ValidatorNotExist
+        );
+
+        require!(
+            self.validator_registry.weight(self.validator.key) > 0
+                && !self
+                    .stake_manager
+                    .era_process_data
+                    .is_bond_settled(self.validator.key),
+            Errors::ValidatorNotSkippable
+        );
+
+        let has_room = self.stake_manager.stake_accounts_len_limit
+            > self.stake_manager.stake_accounts.len() as u64;
+        let qualified = has_room
+            && VoteAccountState::parse(&self.validator)
+                .and_then(|e| e.check_commission(self.stake_manager.max_validator_commission))
+                .is_ok();
+        require!(!qualified, Errors::ValidatorNotSkippable);
+
+        self.stake_manager
+            .era_process_data
+            .skipped_validators
+            .push(self.validator.key());
+
+        // nobody is left to take the rest, it waits in the pool and is bonded next era
+        let rolled_over = if self
+            .validator_registry
+            .remaining_bond_weight(&self.stake_manager.era_process_data)
+            == 0
+        {
+            let need_bond = self.stake_manager.era_process_data.need_bond;
+            self.stake_manager.era_bond += need_bond;
+            // still backing rsol, so counted as active at this era's rate update
+            self.stake_manager.era_process_data.new_active += need_bond;
+            self.stake_manager.era_process_data.need_bond = 0;
+            self.stake_manager.era_process_data.clear_bond_progress();
+            self.stake_manager.advance_era_phase()?;
+            need_bond
+        } else {
+            0
+        };
+
+        emit!(EventEraSkipValidator {
+            era: self.stake_manager.latest_era,
+            validator: self.validator.key(),
+            need_bond: self.stake_manager.era_process_data.need_bond,
+            rolled_over
+        });
+        Ok(())
+    }
+}
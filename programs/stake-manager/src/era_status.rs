@@ -0,0 +1,38 @@
+use crate::{EraPhase, Errors, StakeManager};
+use anchor_lang::prelude::*;
+
+#[derive(Accounts)]
+pub struct EraStatus<'info> {
+    #[account(constraint = stake_manager.is_upgraded() @ Errors::StakeManagerNotUpgraded)]
+    pub stake_manager: Box<Account<'info, StakeManager>>,
+
+    pub clock: Sysvar<'info, Clock>,
+}
+
+#[derive(Clone, Debug, AnchorSerialize, AnchorDeserialize)]
+pub struct EraStatusData {
+    pub era: u64,
+    pub epoch: u64,
+    pub phase: EraPhase,
+    pub need_bond: u64,
+    pub need_unbond: u64,
+    pub pending_stake_accounts_len: u64,
+    pub split_accounts_len: u64,
+}
+
+impl<'info> EraStatus<'info> {
+    // returned through return data so crank bots can plan the next step
+    pub fn process(&self) -> Result<EraStatusData> {
+        let era_process_data = &self.stake_manager.era_process_data;
+
+        Ok(EraStatusData {
+            era: self.stake_manager.latest_era,
+            epoch: self.clock.epoch,
+            phase: self.stake_manager.era_phase,
+            need_bond: era_process_data.need_bond,
+            need_unbond: era_process_data.need_unbond,
+            pending_stake_accounts_len: era_process_data.pending_stake_accounts.len() as u64,
+            split_accounts_len: self.stake_manager.split_accounts.len() as u64,
+        })
+    }
+}
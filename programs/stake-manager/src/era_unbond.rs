@@ -1,4 +1,4 @@
-use crate::{Errors, StakeManager};
+use crate::{pay_crank_tip, EraPhase, Errors, StakeManager, ValidatorRegistry};
 use anchor_lang::prelude::*;
 use anchor_lang::solana_program::sysvar::stake_history;
 use anchor_lang::{
@@ -15,10 +15,20 @@ use anchor_spl::stake::{
 
 #[derive(Accounts)]
 pub struct EraUnbond<'info> {
-    #[account(mut)]
+    #[account(
+        mut,
+        constraint = stake_manager.is_upgraded() @ Errors::StakeManagerNotUpgraded
+    )]
     pub stake_manager: Box<Account<'info, StakeManager>>,
 
     #[account(
+        mut,
+        address = stake_manager.validator_registry @ Errors::ValidatorRegistryNotMatch
+    )]
+    pub validator_registry: Box<Account<'info, ValidatorRegistry>>,
+
+    #[account(
+        mut,
         seeds = [
             &stake_manager.key().to_bytes(),
             StakeManager::POOL_SEED
@@ -68,7 +78,7 @@ pub struct EventEraUnbond {
 impl<'info> EraUnbond<'info> {
     pub fn process(&mut self) -> Result<()> {
         require!(
-            self.stake_manager.era_process_data.need_unbond(),
+            self.stake_manager.era_phase == EraPhase::Unbond,
             Errors::EraNoNeedUnBond
         );
 
@@ -98,6 +108,9 @@ impl<'info> EraUnbond<'info> {
             Errors::ValidatorNotMatch
         );
 
+        self.validator_registry
+            .check_unbond_validator(&delegation.voter_pubkey)?;
+
         let total_need_unbond = self.stake_manager.era_process_data.need_unbond;
 
         let (will_deactive_account, will_deactive_amount) = if delegation.stake <= total_need_unbond
@@ -121,6 +134,8 @@ impl<'info> EraUnbond<'info> {
             self.stake_manager
                 .stake_accounts
                 .retain(|&e| e != self.from_stake_account.key());
+            self.validator_registry
+                .remove_stake_account(&delegation.voter_pubkey)?;
 
             self.stake_manager
                 .era_process_data
@@ -180,8 +195,20 @@ impl<'info> EraUnbond<'info> {
             .split_accounts
             .push(will_deactive_account.key());
 
+        self.validator_registry
+            .add_deactivating_stake(&delegation.voter_pubkey, will_deactive_amount)?;
+
         self.stake_manager.era_process_data.need_unbond -= will_deactive_amount;
 
+        self.stake_manager.advance_era_phase()?;
+
+        pay_crank_tip(
+            &mut self.stake_manager,
+            &self.stake_pool,
+            self.rent_payer.to_account_info(),
+            &self.system_program,
+        )?;
+
         emit!(EventEraUnbond {
             era: self.stake_manager.latest_era,
             from_stake_account: self.from_stake_account.key(),
@@ -0,0 +1,215 @@
+use crate::{pay_crank_tip, EraPhase, Errors, EventEraUnbond, StakeManager, ValidatorRegistry};
+use anchor_lang::prelude::*;
+use anchor_lang::system_program::{create_account, CreateAccount};
+use anchor_lang::{
+    solana_program::{
+        program::invoke_signed,
+        stake::{self, state::StakeStateV2},
+    },
+    system_program,
+};
+use anchor_spl::stake::{
+    deactivate_stake as solana_deactivate_stake, DeactivateStake as SolanaDeactivateStake, Stake,
+    StakeAccount,
+};
+
+#[derive(Accounts)]
+pub struct EraUnbondBatch<'info> {
+    #[account(
+        mut,
+        constraint = stake_manager.is_upgraded() @ Errors::StakeManagerNotUpgraded
+    )]
+    pub stake_manager: Box<Account<'info, StakeManager>>,
+
+    #[account(
+        mut,
+        address = stake_manager.validator_registry @ Errors::ValidatorRegistryNotMatch
+    )]
+    pub validator_registry: Box<Account<'info, ValidatorRegistry>>,
+
+    #[account(
+        mut,
+        seeds = [
+            &stake_manager.key().to_bytes(),
+            StakeManager::POOL_SEED
+        ],
+        bump = stake_manager.pool_seed_bump
+    )]
+    pub stake_pool: SystemAccount<'info>,
+
+    #[account(
+        mut,
+        owner = system_program::ID
+    )]
+    pub rent_payer: Signer<'info>,
+
+    pub clock: Sysvar<'info, Clock>,
+    pub rent: Sysvar<'info, Rent>,
+    pub stake_program: Program<'info, Stake>,
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> EraUnbondBatch<'info> {
+    // remaining accounts are the source stake accounts followed by spare split accounts,
+    // a spare split account is only created when a split is needed so unused ones cost no rent
+    pub fn process(
+        &mut self,
+        remaining_accounts: &'info [AccountInfo<'info>],
+        stake_accounts_len: u8,
+    ) -> Result<()> {
+        require!(
+            self.stake_manager.era_phase == EraPhase::Unbond,
+            Errors::EraNoNeedUnBond
+        );
+
+        let stake_accounts_len = stake_accounts_len as usize;
+        require!(
+            stake_accounts_len > 0 && stake_accounts_len <= remaining_accounts.len(),
+            Errors::RemainingAccountsNotMatch
+        );
+        let (stake_account_infos, spare_split_account_infos) =
+            remaining_accounts.split_at(stake_accounts_len);
+        let mut spare_split_account_infos = spare_split_account_infos.iter();
+
+        for stake_account_info in stake_account_infos {
+            let total_need_unbond = self.stake_manager.era_process_data.need_unbond;
+            if total_need_unbond == 0 {
+                break;
+            }
+
+            let from_stake_account = Account::<StakeAccount>::try_from(stake_account_info)?;
+
+            require!(
+                self.stake_manager
+                    .stake_accounts
+                    .contains(&from_stake_account.key()),
+                Errors::StakeAccountNotExist
+            );
+
+            let delegation = from_stake_account
+                .delegation()
+                .ok_or_else(|| error!(Errors::DelegationEmpty))?;
+
+            require_eq!(
+                delegation.deactivation_epoch,
+                u64::MAX,
+                Errors::StakeAccountNotActive
+            );
+
+            self.validator_registry
+                .check_unbond_validator(&delegation.voter_pubkey)?;
+
+            let (will_deactive_account, will_deactive_amount) =
+                if delegation.stake <= total_need_unbond {
+                    self.stake_manager
+                        .stake_accounts
+                        .retain(|&e| e != from_stake_account.key());
+                    self.validator_registry
+                        .remove_stake_account(&delegation.voter_pubkey)?;
+
+                    self.stake_manager
+                        .era_process_data
+                        .pending_stake_accounts
+                        .retain(|&e| e != from_stake_account.key());
+
+                    (stake_account_info.clone(), delegation.stake)
+                } else {
+                    let split_stake_account_info = spare_split_account_infos
+                        .next()
+                        .ok_or_else(|| error!(Errors::RemainingAccountsNotMatch))?;
+
+                    require!(
+                        !self
+                            .stake_manager
+                            .split_accounts
+                            .contains(split_stake_account_info.key),
+                        Errors::SplitStakeAccountAlreadyExist
+                    );
+
+                    let space = std::mem::size_of::<StakeStateV2>();
+                    create_account(
+                        CpiContext::new(
+                            self.system_program.to_account_info(),
+                            CreateAccount {
+                                from: self.rent_payer.to_account_info(),
+                                to: split_stake_account_info.clone(),
+                            },
+                        ),
+                        self.rent.minimum_balance(space),
+                        space as u64,
+                        &stake::program::ID,
+                    )?;
+
+                    // split
+                    let split_instruction = stake::instruction::split(
+                        stake_account_info.key,
+                        self.stake_pool.key,
+                        total_need_unbond,
+                        split_stake_account_info.key,
+                    )
+                    .last()
+                    .unwrap()
+                    .clone();
+
+                    invoke_signed(
+                        &split_instruction,
+                        &[
+                            self.stake_program.to_account_info(),
+                            stake_account_info.clone(),
+                            split_stake_account_info.clone(),
+                            self.stake_pool.to_account_info(),
+                        ],
+                        &[&[
+                            &self.stake_manager.key().to_bytes(),
+                            StakeManager::POOL_SEED,
+                            &[self.stake_manager.pool_seed_bump],
+                        ]],
+                    )?;
+
+                    (split_stake_account_info.clone(), total_need_unbond)
+                };
+
+            // deactive
+            solana_deactivate_stake(CpiContext::new_with_signer(
+                self.stake_program.to_account_info(),
+                SolanaDeactivateStake {
+                    stake: will_deactive_account.clone(),
+                    staker: self.stake_pool.to_account_info(),
+                    clock: self.clock.to_account_info(),
+                },
+                &[&[
+                    &self.stake_manager.key().to_bytes(),
+                    StakeManager::POOL_SEED,
+                    &[self.stake_manager.pool_seed_bump],
+                ]],
+            ))?;
+
+            self.stake_manager
+                .split_accounts
+                .push(will_deactive_account.key());
+
+            self.validator_registry
+                .add_deactivating_stake(&delegation.voter_pubkey, will_deactive_amount)?;
+
+            self.stake_manager.era_process_data.need_unbond -= will_deactive_amount;
+
+            emit!(EventEraUnbond {
+                era: self.stake_manager.latest_era,
+                from_stake_account: from_stake_account.key(),
+                split_account: will_deactive_account.key(),
+                unbond_amount: will_deactive_amount
+            });
+        }
+
+        self.stake_manager.advance_era_phase()?;
+
+        pay_crank_tip(
+            &mut self.stake_manager,
+            &self.stake_pool,
+            self.rent_payer.to_account_info(),
+            &self.system_program,
+        )?;
+
+        Ok(())
+    }
+}
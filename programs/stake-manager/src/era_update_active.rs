@@ -1,13 +1,47 @@
-use crate::{Errors, StakeManager};
+use crate::{
+    pay_crank_tip, record_delinquent_stake, EraPhase, Errors, EventDeactivateDelinquent,
+    StakeManager, ValidatorRegistry,
+};
 use anchor_lang::prelude::*;
-use anchor_spl::stake::StakeAccount;
+use anchor_lang::solana_program::sysvar::stake_history;
+use anchor_spl::stake::{withdraw, Stake, StakeAccount, Withdraw};
 
 #[derive(Accounts)]
 pub struct EraUpdateActive<'info> {
-    #[account(mut)]
+    #[account(
+        mut,
+        constraint = stake_manager.is_upgraded() @ Errors::StakeManagerNotUpgraded
+    )]
     pub stake_manager: Box<Account<'info, StakeManager>>,
 
+    #[account(
+        mut,
+        address = stake_manager.validator_registry @ Errors::ValidatorRegistryNotMatch
+    )]
+    pub validator_registry: Box<Account<'info, ValidatorRegistry>>,
+
+    #[account(mut)]
     pub stake_account: Account<'info, StakeAccount>,
+
+    #[account(
+        mut,
+        seeds = [
+            &stake_manager.key().to_bytes(),
+            StakeManager::POOL_SEED
+        ],
+        bump = stake_manager.pool_seed_bump
+    )]
+    pub stake_pool: SystemAccount<'info>,
+
+    #[account(mut)]
+    pub cranker: Signer<'info>,
+
+    pub clock: Sysvar<'info, Clock>,
+    /// CHECK: stake history
+    #[account(address = stake_history::ID)]
+    pub stake_history: UncheckedAccount<'info>,
+    pub stake_program: Program<'info, Stake>,
+    pub system_program: Program<'info, System>,
 }
 
 #[event]
@@ -15,12 +49,58 @@ pub struct EventEraUpdateActive {
     pub era: u64,
     pub stake_account: Pubkey,
     pub stake_amount: u64,
+    pub excess_amount: u64,
+}
+
+// lamports above the delegated stake and rent reserve, such as mev tips and direct transfers,
+// are moved to the pool and restaked next era
+pub fn withdraw_stake_account_excess<'info>(
+    stake_manager: &Account<'info, StakeManager>,
+    stake_account: &Account<'info, StakeAccount>,
+    stake_amount: u64,
+    stake_pool: &SystemAccount<'info>,
+    clock: &Sysvar<'info, Clock>,
+    stake_history: &UncheckedAccount<'info>,
+    stake_program: &Program<'info, Stake>,
+) -> Result<u64> {
+    let meta = stake_account
+        .meta()
+        .ok_or_else(|| error!(Errors::DelegationEmpty))?;
+    let excess_amount = stake_account
+        .get_lamports()
+        .saturating_sub(stake_amount)
+        .saturating_sub(meta.rent_exempt_reserve);
+    if excess_amount == 0 {
+        return Ok(0);
+    }
+
+    withdraw(
+        CpiContext::new_with_signer(
+            stake_program.to_account_info(),
+            Withdraw {
+                stake: stake_account.to_account_info(),
+                withdrawer: stake_pool.to_account_info(),
+                to: stake_pool.to_account_info(),
+                clock: clock.to_account_info(),
+                stake_history: stake_history.to_account_info(),
+            },
+            &[&[
+                &stake_manager.key().to_bytes(),
+                StakeManager::POOL_SEED,
+                &[stake_manager.pool_seed_bump],
+            ]],
+        ),
+        excess_amount,
+        None,
+    )?;
+
+    Ok(excess_amount)
 }
 
 impl<'info> EraUpdateActive<'info> {
     pub fn process(&mut self) -> Result<()> {
         require!(
-            self.stake_manager.era_process_data.need_update_active(),
+            self.stake_manager.era_phase == EraPhase::UpdateActive,
             Errors::EraNoNeedUpdateActive
         );
 
@@ -37,24 +117,73 @@ impl<'info> EraUpdateActive<'info> {
             .delegation()
             .ok_or_else(|| error!(Errors::DelegationEmpty))?;
 
-        // require stake is active (deactivation_epoch == u64::MAX)
-        require_eq!(
-            delegation.deactivation_epoch,
-            std::u64::MAX,
-            Errors::StakeAccountNotActive
-        );
+        let era = self.stake_manager.latest_era;
+        // deactivated by someone else as delinquent, dropped instead of blocking the era
+        let (stake_amount, excess_amount) = if delegation.deactivation_epoch != u64::MAX {
+            record_delinquent_stake(
+                &mut self.stake_manager,
+                &mut self.validator_registry,
+                &self.stake_account.key(),
+                &delegation,
+            )?;
+
+            emit!(EventDeactivateDelinquent {
+                era,
+                validator: delegation.voter_pubkey,
+                stake_account: self.stake_account.key(),
+                stake_amount: delegation.stake
+            });
+            (0, 0)
+        } else {
+            self.stake_manager
+                .era_process_data
+                .pending_stake_accounts
+                .retain(|&e| e != self.stake_account.key());
+
+            let excess_amount = withdraw_stake_account_excess(
+                &self.stake_manager,
+                &self.stake_account,
+                delegation.stake,
+                &self.stake_pool,
+                &self.clock,
+                &self.stake_history,
+                &self.stake_program,
+            )?;
+
+            self.validator_registry.update_active_stake(
+                &delegation.voter_pubkey,
+                delegation.stake,
+                era,
+            )?;
+            (delegation.stake, excess_amount)
+        };
 
-        self.stake_manager
+        self.stake_manager.era_process_data.new_active += stake_amount + excess_amount;
+        self.stake_manager.era_bond += excess_amount;
+
+        if self
+            .stake_manager
             .era_process_data
             .pending_stake_accounts
-            .retain(|&e| e != self.stake_account.key());
+            .is_empty()
+        {
+            self.validator_registry.clear_uncounted_stake(era);
+        }
+
+        self.stake_manager.advance_era_phase()?;
 
-        self.stake_manager.era_process_data.new_active += delegation.stake;
+        pay_crank_tip(
+            &mut self.stake_manager,
+            &self.stake_pool,
+            self.cranker.to_account_info(),
+            &self.system_program,
+        )?;
 
         emit!(EventEraUpdateActive {
             era: self.stake_manager.latest_era,
             stake_account: self.stake_account.key(),
-            stake_amount: delegation.stake
+            stake_amount,
+            excess_amount
         });
         Ok(())
     }
@@ -0,0 +1,163 @@
+use crate::{
+    pay_crank_tip, record_delinquent_stake, withdraw_stake_account_excess, EraPhase, Errors,
+    EventDeactivateDelinquent, EventEraUpdateActive, StakeManager, ValidatorRegistry,
+};
+use anchor_lang::prelude::*;
+use anchor_lang::solana_program::sysvar::stake_history;
+use anchor_spl::stake::{Stake, StakeAccount};
+
+#[derive(Accounts)]
+pub struct EraUpdateActiveBatch<'info> {
+    #[account(
+        mut,
+        constraint = stake_manager.is_upgraded() @ Errors::StakeManagerNotUpgraded
+    )]
+    pub stake_manager: Box<Account<'info, StakeManager>>,
+
+    #[account(
+        mut,
+        address = stake_manager.validator_registry @ Errors::ValidatorRegistryNotMatch
+    )]
+    pub validator_registry: Box<Account<'info, ValidatorRegistry>>,
+
+    #[account(
+        mut,
+        seeds = [
+            &stake_manager.key().to_bytes(),
+            StakeManager::POOL_SEED
+        ],
+        bump = stake_manager.pool_seed_bump
+    )]
+    pub stake_pool: SystemAccount<'info>,
+
+    #[account(mut)]
+    pub cranker: Signer<'info>,
+
+    pub clock: Sysvar<'info, Clock>,
+    /// CHECK: stake history
+    #[account(address = stake_history::ID)]
+    pub stake_history: UncheckedAccount<'info>,
+    pub stake_program: Program<'info, Stake>,
+    pub system_program: Program<'info, System>,
+}
+
+#[event]
+pub struct EventEraUpdateActiveBatch {
+    pub era: u64,
+    pub stake_accounts_len: u64,
+    pub stake_amount: u64,
+    pub excess_amount: u64,
+}
+
+impl<'info> EraUpdateActiveBatch<'info> {
+    // stake accounts are passed as remaining accounts
+    pub fn process(&mut self, stake_account_infos: &'info [AccountInfo<'info>]) -> Result<()> {
+        require!(
+            self.stake_manager.era_phase == EraPhase::UpdateActive,
+            Errors::EraNoNeedUpdateActive
+        );
+
+        require!(
+            !stake_account_infos.is_empty(),
+            Errors::RemainingAccountsNotMatch
+        );
+
+        let mut total_stake_amount: u64 = 0;
+        let mut total_excess_amount: u64 = 0;
+        for stake_account_info in stake_account_infos {
+            let stake_account = Account::<StakeAccount>::try_from(stake_account_info)?;
+
+            require!(
+                self.stake_manager
+                    .era_process_data
+                    .pending_stake_accounts
+                    .contains(&stake_account.key()),
+                Errors::StakeAccountNotExist
+            );
+
+            let delegation = stake_account
+                .delegation()
+                .ok_or_else(|| error!(Errors::DelegationEmpty))?;
+
+            // deactivated by someone else as delinquent, dropped instead of blocking the era
+            if delegation.deactivation_epoch != u64::MAX {
+                record_delinquent_stake(
+                    &mut self.stake_manager,
+                    &mut self.validator_registry,
+                    &stake_account.key(),
+                    &delegation,
+                )?;
+
+                emit!(EventDeactivateDelinquent {
+                    era: self.stake_manager.latest_era,
+                    validator: delegation.voter_pubkey,
+                    stake_account: stake_account.key(),
+                    stake_amount: delegation.stake
+                });
+                continue;
+            }
+
+            self.stake_manager
+                .era_process_data
+                .pending_stake_accounts
+                .retain(|&e| e != stake_account.key());
+
+            let excess_amount = withdraw_stake_account_excess(
+                &self.stake_manager,
+                &stake_account,
+                delegation.stake,
+                &self.stake_pool,
+                &self.clock,
+                &self.stake_history,
+                &self.stake_program,
+            )?;
+
+            let era = self.stake_manager.latest_era;
+            self.validator_registry.update_active_stake(
+                &delegation.voter_pubkey,
+                delegation.stake,
+                era,
+            )?;
+
+            total_stake_amount += delegation.stake;
+            total_excess_amount += excess_amount;
+
+            emit!(EventEraUpdateActive {
+                era: self.stake_manager.latest_era,
+                stake_account: stake_account.key(),
+                stake_amount: delegation.stake,
+                excess_amount
+            });
+        }
+
+        self.stake_manager.era_process_data.new_active += total_stake_amount + total_excess_amount;
+        self.stake_manager.era_bond += total_excess_amount;
+
+        if self
+            .stake_manager
+            .era_process_data
+            .pending_stake_accounts
+            .is_empty()
+        {
+            self.validator_registry
+                .clear_uncounted_stake(self.stake_manager.latest_era);
+        }
+
+        self.stake_manager.advance_era_phase()?;
+
+        pay_crank_tip(
+            &mut self.stake_manager,
+            &self.stake_pool,
+            self.cranker.to_account_info(),
+            &self.system_program,
+        )?;
+
+        emit!(EventEraUpdateActiveBatch {
+            era: self.stake_manager.latest_era,
+            stake_accounts_len: stake_account_infos.len() as u64,
+            stake_amount: total_stake_amount,
+            excess_amount: total_excess_amount
+        });
+        Ok(())
+    }
+}
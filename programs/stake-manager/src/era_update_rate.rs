@@ -1,4 +1,4 @@
-use crate::{Errors, StakeManager};
+use crate::{pay_crank_tip, EraHistory, EraPhase, EraRecord, Errors, StakeManager};
 use anchor_lang::prelude::*;
 use anchor_spl::token::{Mint, Token, TokenAccount};
 
@@ -9,11 +9,19 @@ use mint_manager_program::{self, MintManager};
 pub struct EraUpdateRate<'info> {
     #[account(
         mut, 
-        has_one = fee_recipient @ Errors::FeeRecipientNotMatch
+        has_one = fee_recipient @ Errors::FeeRecipientNotMatch,
+        constraint = stake_manager.is_upgraded() @ Errors::StakeManagerNotUpgraded
     )]
     pub stake_manager: Box<Account<'info, StakeManager>>,
 
     #[account(
+        mut,
+        address = stake_manager.era_history @ Errors::EraHistoryNotMatch
+    )]
+    pub era_history: Box<Account<'info, EraHistory>>,
+
+    #[account(
+        mut,
         seeds = [
             &stake_manager.key().to_bytes(),
             StakeManager::POOL_SEED,
@@ -22,6 +30,9 @@ pub struct EraUpdateRate<'info> {
     )]
     pub stake_pool: SystemAccount<'info>,
 
+    #[account(mut)]
+    pub cranker: Signer<'info>,
+
     pub mint_manager: Box<Account<'info, MintManager>>,
 
     #[account(mut)]
@@ -38,6 +49,7 @@ pub struct EraUpdateRate<'info> {
 
     pub mint_manager_program: Program<'info, MintManagerProgram>,
     pub token_program: Program<'info, Token>,
+    pub system_program: Program<'info, System>,
 }
 
 #[event]
@@ -45,15 +57,29 @@ pub struct EventEraUpdateRate {
     pub era: u64,
     pub rate: u64,
     pub fee: u64,
+    pub referral_fee: u64,
+    pub bridged_epochs: u64,
+    pub crank_tip_fee: u64,
+    pub pool_excess: u64,
 }
 
 impl<'info> EraUpdateRate<'info> {
-    pub fn process(&mut self) -> Result<()> {
+    // the (min_rate, max_rate) bounds replace the rate change limit when the admin approves
+    // an override, returns the observed rate change
+    pub fn process(&mut self, rate_bounds: Option<(u64, u64)>) -> Result<u64> {
         require!(
-            self.stake_manager.era_process_data.need_update_rate(),
+            self.stake_manager.era_phase == EraPhase::UpdateRate,
             Errors::EraNoNeedUpdateRate
         );
 
+        // stray lamports in the pool count as reward and are restaked next era,
+        // withdrawn delinquent stake arrives the same way and leaves delinquent_stake
+        let pool_excess = self.stake_manager.calc_pool_excess(self.stake_pool.lamports());
+        self.stake_manager.delinquent_stake -= pool_excess.min(self.stake_manager.delinquent_stake);
+        self.stake_manager.era_process_data.new_active +=
+            pool_excess + self.stake_manager.delinquent_stake;
+        self.stake_manager.era_bond += pool_excess;
+
         let reward = if self.stake_manager.era_process_data.new_active
             > self.stake_manager.era_process_data.old_active
         {
@@ -63,8 +89,23 @@ impl<'info> EraUpdateRate<'info> {
             0
         };
 
+        // the crank tip fund unbonded since the last update is repaid out of the protocol fee
         let protocol_fee = self.stake_manager.calc_protocol_fee(reward)?;
-        if protocol_fee > 0 {
+        let crank_tip_rsol = self
+            .stake_manager
+            .calc_rsol_amount(self.stake_manager.crank_tip_unsettled)?;
+        let crank_tip_fee = protocol_fee.min(crank_tip_rsol);
+        self.stake_manager.crank_tip_unsettled = if crank_tip_fee == crank_tip_rsol {
+            0
+        } else {
+            self.stake_manager.crank_tip_unsettled.saturating_sub(
+                self.stake_manager.calc_sol_amount(crank_tip_fee)?,
+            )
+        };
+        let protocol_fee = protocol_fee - crank_tip_fee;
+
+        let referral_fee = self.stake_manager.calc_referral_fee(protocol_fee)?;
+        if protocol_fee > referral_fee {
             let cpi_program = self.mint_manager_program.to_account_info();
             let cpi_accounts = MintToken {
                 mint_manager: self.mint_manager.to_account_info(),
@@ -80,12 +121,18 @@ impl<'info> EraUpdateRate<'info> {
                     StakeManager::POOL_SEED,
                     &[self.stake_manager.pool_seed_bump],
                 ]]),
-                protocol_fee,
+                protocol_fee - referral_fee,
             )?;
+        }
 
-            self.stake_manager.total_protocol_fee += protocol_fee;
-            self.stake_manager.total_rsol_supply += protocol_fee;
+        // referral fee is minted when referrers claim it
+        if referral_fee > 0 {
+            self.stake_manager.referral_reward_per_rsol += (referral_fee as u128)
+                * StakeManager::REWARD_PER_RSOL_BASE
+                / (self.stake_manager.total_referred_rsol as u128);
         }
+        self.stake_manager.total_protocol_fee += protocol_fee;
+        self.stake_manager.total_rsol_supply += protocol_fee;
 
         let cal_temp = self.stake_manager.active + self.stake_manager.era_process_data.new_active;
         let new_active = if cal_temp > self.stake_manager.era_process_data.old_active {
@@ -100,22 +147,59 @@ impl<'info> EraUpdateRate<'info> {
         let rate_change = self
             .stake_manager
             .calc_rate_change(self.stake_manager.rate, new_rate)?;
-        require_gte!(
-            self.stake_manager.rate_change_limit,
-            rate_change,
-            Errors::RateChangeOverLimit
-        );
+        match rate_bounds {
+            Some((min_rate, max_rate)) => {
+                require_gte!(new_rate, min_rate, Errors::RateUnderMinAcceptable);
+                require_gte!(max_rate, new_rate, Errors::RateOverMaxAcceptable);
+            }
+            None => {
+                let rate_change_limit = self
+                    .stake_manager
+                    .era_process_data
+                    .rate_change_limit(self.stake_manager.rate_change_limit);
+                require_gte!(
+                    rate_change_limit,
+                    rate_change,
+                    Errors::RateChangeOverLimit
+                );
+            }
+        }
 
         self.stake_manager.era_process_data.old_active = 0;
         self.stake_manager.era_process_data.new_active = 0;
         self.stake_manager.active = new_active;
         self.stake_manager.rate = new_rate;
 
+        self.era_history.push(EraRecord {
+            era: self.stake_manager.latest_era,
+            rate: new_rate,
+            active: new_active,
+            total_rsol_supply: self.stake_manager.total_rsol_supply,
+            reward,
+            protocol_fee,
+        });
+
+        self.stake_manager.advance_era_phase()?;
+
+        // the admin override is not a crank step
+        if rate_bounds.is_none() {
+            pay_crank_tip(
+                &mut self.stake_manager,
+                &self.stake_pool,
+                self.cranker.to_account_info(),
+                &self.system_program,
+            )?;
+        }
+
         emit!(EventEraUpdateRate{ 
             era: self.stake_manager.latest_era, 
             rate: new_rate,
-            fee: protocol_fee
+            fee: protocol_fee,
+            referral_fee,
+            bridged_epochs: self.stake_manager.era_process_data.bridged_epochs,
+            crank_tip_fee,
+            pool_excess
         });
-        Ok(())
+        Ok(rate_change)
     }
 }
@@ -0,0 +1,57 @@
+use crate::era_update_rate::*;
+use crate::Errors;
+use anchor_lang::prelude::*;
+
+#[derive(Accounts)]
+pub struct EraUpdateRateOverride<'info> {
+    pub update_rate: EraUpdateRate<'info>,
+
+    #[account(
+        address = update_rate.stake_manager.admin @ Errors::AdminNotMatch
+    )]
+    pub admin: Signer<'info>,
+}
+
+#[event]
+pub struct EventEraUpdateRateOverride {
+    pub era: u64,
+    pub admin: Pubkey,
+    pub old_rate: u64,
+    pub new_rate: u64,
+    pub min_rate: u64,
+    pub max_rate: u64,
+    pub rate_change: u64,
+    pub rate_change_limit: u64,
+}
+
+impl<'info> EraUpdateRateOverride<'info> {
+    // lets a slashed or unusually rewarded era finish when the rate change is over the limit
+    // the new rate must land inside the admin's bounds in both directions
+    pub fn process(&mut self, min_rate: u64, max_rate: u64) -> Result<()> {
+        require_gte!(max_rate, min_rate, Errors::RateOverMaxAcceptable);
+
+        let old_rate = self.update_rate.stake_manager.rate;
+        let rate_change_limit = self
+            .update_rate
+            .stake_manager
+            .era_process_data
+            .rate_change_limit(self.update_rate.stake_manager.rate_change_limit);
+
+        let rate_change = self.update_rate.process(Some((min_rate, max_rate)))?;
+
+        let stake_manager = &mut self.update_rate.stake_manager;
+        stake_manager.last_rate_override_era = stake_manager.latest_era;
+
+        emit!(EventEraUpdateRateOverride {
+            era: stake_manager.latest_era,
+            admin: self.admin.key(),
+            old_rate,
+            new_rate: stake_manager.rate,
+            min_rate,
+            max_rate,
+            rate_change,
+            rate_change_limit
+        });
+        Ok(())
+    }
+}
@@ -1,11 +1,14 @@
-use crate::{Errors, StakeManager};
+use crate::{pay_crank_tip, Errors, StakeManager};
 use anchor_lang::prelude::*;
-use anchor_lang::solana_program::sysvar::stake_history;
+use anchor_lang::solana_program::sysvar::stake_history::{self, StakeHistory};
 use anchor_spl::stake::{withdraw, Stake, StakeAccount, Withdraw};
 
 #[derive(Accounts)]
 pub struct EraWithdraw<'info> {
-    #[account(mut)]
+    #[account(
+        mut,
+        constraint = stake_manager.is_upgraded() @ Errors::StakeManagerNotUpgraded
+    )]
     pub stake_manager: Box<Account<'info, StakeManager>>,
 
     #[account(
@@ -18,6 +21,9 @@ pub struct EraWithdraw<'info> {
     )]
     pub stake_pool: SystemAccount<'info>,
 
+    #[account(mut)]
+    pub cranker: Signer<'info>,
+
     #[account(mut)]
     pub stake_account: Account<'info, StakeAccount>,
 
@@ -26,6 +32,7 @@ pub struct EraWithdraw<'info> {
     #[account(address = stake_history::ID)]
     pub stake_history: UncheckedAccount<'info>,
     pub stake_program: Program<'info, Stake>,
+    pub system_program: Program<'info, System>,
 }
 
 #[event]
@@ -33,6 +40,7 @@ pub struct EventEraWithdraw {
     pub era: u64,
     pub stake_account: Pubkey,
     pub withdraw_amount: u64,
+    pub remaining_amount: u64,
 }
 
 impl<'info> EraWithdraw<'info> {
@@ -55,7 +63,30 @@ impl<'info> EraWithdraw<'info> {
             Errors::StakeAccountActive
         );
 
-        let withdraw_amount = self.stake_account.get_lamports();
+        // assume the slower cooldown rate so the amount never exceeds what the stake program allows
+        let stake_history = StakeHistory::from_account_info(&self.stake_history)?;
+        let effective_stake = delegation.stake(self.clock.epoch, Some(&stake_history), Some(0));
+        let meta = self
+            .stake_account
+            .meta()
+            .ok_or_else(|| error!(Errors::DelegationEmpty))?;
+
+        // a still cooling account keeps its effective stake and rent reserve
+        let lamports = self.stake_account.get_lamports();
+        let withdraw_amount = if effective_stake == 0 {
+            lamports
+        } else {
+            lamports
+                .saturating_sub(effective_stake)
+                .saturating_sub(meta.rent_exempt_reserve)
+        };
+        // nothing has cooled down yet, report the epoch it is expected to
+        if withdraw_amount == 0 {
+            let withdrawable_epoch =
+                calc_withdrawable_epoch(effective_stake, self.clock.epoch, &stake_history);
+            msg!("stake account withdrawable at epoch {}", withdrawable_epoch);
+            return err!(Errors::StakeAccountNotWithdrawable);
+        }
         withdraw(
             CpiContext::new_with_signer(
                 self.stake_program.to_account_info(),
@@ -76,15 +107,111 @@ impl<'info> EraWithdraw<'info> {
             None,
         )?;
 
-        self.stake_manager
-            .split_accounts
-            .retain(|&e| e != self.stake_account.key());
+        let remaining_amount = lamports - withdraw_amount;
+        // only a fully withdrawn account is tipped, partial withdraws can be repeated at will
+        if remaining_amount == 0 {
+            self.stake_manager
+                .split_accounts
+                .retain(|&e| e != self.stake_account.key());
+
+            pay_crank_tip(
+                &mut self.stake_manager,
+                &self.stake_pool,
+                self.cranker.to_account_info(),
+                &self.system_program,
+            )?;
+        }
 
         emit!(EventEraWithdraw {
             era: self.stake_manager.latest_era,
             stake_account: self.stake_account.key(),
-            withdraw_amount
+            withdraw_amount,
+            remaining_amount
         });
         Ok(())
     }
 }
+
+// the cooldown is projected past the latest stake history entry as if the cluster kept
+// its last known effective and deactivating stake, same per-epoch step as the stake program
+// at its 9% cooldown rate
+fn calc_withdrawable_epoch(effective_stake: u64, epoch: u64, stake_history: &StakeHistory) -> u64 {
+    const MAX_PROJECTED_EPOCHS: u64 = 64;
+    const COOLDOWN_RATE_BPS: u128 = 900;
+
+    let Some(cluster_stake) = stake_history.get(epoch.saturating_sub(1)) else {
+        return epoch + 1;
+    };
+
+    let mut remaining_stake = effective_stake;
+    let mut withdrawable_epoch = epoch;
+    while remaining_stake > 0 && withdrawable_epoch < epoch + MAX_PROJECTED_EPOCHS {
+        withdrawable_epoch += 1;
+        if cluster_stake.deactivating == 0 {
+            break;
+        }
+
+        let newly_not_effective_stake = u64::try_from(
+            (remaining_stake as u128) * (cluster_stake.effective as u128)
+                / (cluster_stake.deactivating as u128)
+                * COOLDOWN_RATE_BPS
+                / 10_000,
+        )
+        .unwrap_or(u64::MAX)
+        .max(1);
+        remaining_stake = remaining_stake.saturating_sub(newly_not_effective_stake);
+    }
+    withdrawable_epoch
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anchor_lang::solana_program::stake_history::StakeHistoryEntry;
+
+    fn stake_history(epoch: u64, effective: u64, deactivating: u64) -> StakeHistory {
+        let mut stake_history = StakeHistory::default();
+        stake_history.add(
+            epoch,
+            StakeHistoryEntry {
+                effective,
+                activating: 0,
+                deactivating,
+            },
+        );
+        stake_history
+    }
+
+    #[test]
+    fn test_withdrawable_epoch_no_history() {
+        let stake_history = StakeHistory::default();
+        assert_eq!(calc_withdrawable_epoch(1_000, 100, &stake_history), 101);
+    }
+
+    #[test]
+    fn test_withdrawable_epoch_single_epoch() {
+        // the cluster cools down far more than the account holds
+        let stake_history = stake_history(99, 1_000_000, 1_000);
+        assert_eq!(calc_withdrawable_epoch(1_000, 100, &stake_history), 101);
+    }
+
+    #[test]
+    fn test_withdrawable_epoch_across_cooldown() {
+        // 90% of the remaining stake cools down each epoch: 1_000, 100, 10, 1
+        let stake_history = stake_history(99, 10_000, 1_000);
+        assert_eq!(calc_withdrawable_epoch(1_000, 100, &stake_history), 104);
+    }
+
+    #[test]
+    fn test_withdrawable_epoch_nothing_deactivating() {
+        let stake_history = stake_history(99, 10_000, 0);
+        assert_eq!(calc_withdrawable_epoch(1_000, 100, &stake_history), 101);
+    }
+
+    #[test]
+    fn test_withdrawable_epoch_projection_cap() {
+        // at least a lamport cools down each epoch, the projection stops after 64 epochs
+        let stake_history = stake_history(99, 1, u64::MAX);
+        assert_eq!(calc_withdrawable_epoch(1_000, 100, &stake_history), 164);
+    }
+}
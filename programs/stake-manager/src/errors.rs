@@ -121,4 +121,112 @@ pub enum Errors {
 
     #[msg("Stake accounts len over limit")]
     StakeAccountsLenOverLimit,
+
+    #[msg("Slippage exceeded")]
+    SlippageExceeded,
+
+    #[msg("Reserve not enabled")]
+    ReserveNotEnabled,
+
+    #[msg("Reserve balance not enough")]
+    ReserveBalanceNotEnough,
+
+    #[msg("Reserve no need rebalance")]
+    ReserveNoNeedRebalance,
+
+    #[msg("Invalid fee commission")]
+    InvalidFeeCommission,
+
+    #[msg("Unstake tickets over limit")]
+    UnstakeTicketsOverLimit,
+
+    #[msg("Unstake account not cancelable")]
+    UnstakeAccountNotCancelable,
+
+    #[msg("Referrer not match")]
+    ReferrerNotMatch,
+
+    #[msg("Referral reward is zero")]
+    ReferralRewardIsZero,
+
+    #[msg("Validator bond over target")]
+    ValidatorBondOverTarget,
+
+    #[msg("Invalid crank tip")]
+    InvalidCrankTip,
+
+    #[msg("Era phase transition invalid")]
+    EraPhaseTransitionInvalid,
+
+    #[msg("Rate over max acceptable")]
+    RateOverMaxAcceptable,
+
+    #[msg("Era history not match")]
+    EraHistoryNotMatch,
+
+    #[msg("Stake account not withdrawable until the required epoch")]
+    StakeAccountNotWithdrawable,
+
+    #[msg("Validator registry not match")]
+    ValidatorRegistryNotMatch,
+
+    #[msg("Validator registry is full")]
+    ValidatorRegistryFull,
+
+    #[msg("Invalid vote account")]
+    InvalidVoteAccount,
+
+    #[msg("Validator commission too high")]
+    ValidatorCommissionTooHigh,
+
+    #[msg("Validator not voting")]
+    ValidatorNotVoting,
+
+    #[msg("Validator delinquent")]
+    ValidatorDelinquent,
+
+    #[msg("Unbond draining validator first")]
+    UnbondDrainingValidatorFirst,
+
+    #[msg("Validator not over allocated")]
+    ValidatorNotOverAllocated,
+
+    #[msg("Validator not under allocated")]
+    ValidatorNotUnderAllocated,
+
+    #[msg("Rebalance amount over limit")]
+    RebalanceAmountOverLimit,
+
+    #[msg("Stake manager not upgraded")]
+    StakeManagerNotUpgraded,
+
+    #[msg("Stake manager already upgraded")]
+    StakeManagerAlreadyUpgraded,
+
+    #[msg("Self referral")]
+    SelfReferral,
+
+    #[msg("Validator not skippable")]
+    ValidatorNotSkippable,
+
+    #[msg("Unsupported vote state version")]
+    UnsupportedVoteStateVersion,
+
+    #[msg("Validator not delinquent")]
+    ValidatorNotDelinquent,
+
+    #[msg("Split accounts len over limit")]
+    SplitAccountsLenOverLimit,
+
+    #[msg("Rebalance amount too low")]
+    RebalanceAmountTooLow,
+
+    #[msg("Fee refund not approved")]
+    FeeRefundNotApproved,
+
+    #[msg("Rate under min acceptable")]
+    RateUnderMinAcceptable,
+
+    #[msg("Validator not active")]
+    ValidatorNotActive,
 }
@@ -1,6 +1,6 @@
 pub use crate::errors::Errors;
-use crate::EraProcessData;
 pub use crate::StakeManager;
+use crate::{EraPhase, EraProcessData};
 use anchor_lang::prelude::*;
 use anchor_spl::token::{Mint, TokenAccount};
 
@@ -90,7 +90,39 @@ impl<'info> Initialize<'info> {
                 old_active: 0,
                 new_active: 0,
                 pending_stake_accounts: vec![],
+                bonded_validators: vec![],
+                skipped_validators: vec![],
+                bridged_epochs: 0,
             },
+            reserve_seed_bump: 0,
+            reserve_target: 0,
+            reserve_balance: 0,
+            reserve_fee_collected: 0,
+            era_reserve_fill: 0,
+            era_reserve_release: 0,
+            instant_unstake_min_fee_commission:
+                StakeManager::DEFAULT_INSTANT_UNSTAKE_MIN_FEE_COMMISSION,
+            instant_unstake_max_fee_commission:
+                StakeManager::DEFAULT_INSTANT_UNSTAKE_MAX_FEE_COMMISSION,
+            cancel_unstake_fee_refund: false,
+            referral_fee_share: 0,
+            total_referred_rsol: 0,
+            referral_reward_per_rsol: 0,
+            crank_tip: 0,
+            crank_tip_era_budget: 0,
+            crank_tip_era_paid: 0,
+            crank_tip_unsettled: 0,
+            crank_tip_fund: 0,
+            total_crank_tip_paid: 0,
+            era_phase: EraPhase::Idle,
+            total_unstake_unclaimed: 0,
+            last_rate_override_era: 0,
+            era_history: Pubkey::default(),
+            validator_registry: Pubkey::default(),
+            max_validator_commission: StakeManager::DEFAULT_MAX_VALIDATOR_COMMISSION,
+            delinquent_stake: 0,
+            min_rebalance_amount: StakeManager::DEFAULT_MIN_REBALANCE_AMOUNT,
+            version: StakeManager::VERSION,
         });
 
         Ok(())
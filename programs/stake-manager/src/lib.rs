@@ -1,44 +1,84 @@
 use anchor_lang::{prelude::*, Bumps};
 
 pub mod admin;
+pub mod crank_tip;
+pub mod deactivate_delinquent;
 pub mod era_bond;
 pub mod era_merge;
 pub mod era_new;
+pub mod era_skip_validator;
+pub mod era_status;
 pub mod era_unbond;
+pub mod era_unbond_batch;
 pub mod era_update_active;
+pub mod era_update_active_batch;
 pub mod era_update_rate;
+pub mod era_update_rate_override;
 pub mod era_withdraw;
 pub mod errors;
 pub mod initialize;
+pub mod rebalance;
+pub mod rebalance_reserve;
 pub mod redelegate;
+pub mod referral;
+pub mod staker_cancel_unstake;
+pub mod staker_deposit_stake_account;
+pub mod staker_instant_unstake;
 pub mod staker_stake;
+pub mod staker_transfer_unstake_ticket;
 pub mod staker_unstake;
 pub mod staker_withdraw;
+pub mod staker_withdraw_batch;
+pub mod staker_withdraw_stake;
 pub mod states;
+pub mod vote_account;
 
 pub use crate::admin::*;
+pub use crate::crank_tip::*;
+pub use crate::deactivate_delinquent::*;
 pub use crate::era_bond::*;
 pub use crate::era_merge::*;
 pub use crate::era_new::*;
+pub use crate::era_skip_validator::*;
+pub use crate::era_status::*;
 pub use crate::era_unbond::*;
+pub use crate::era_unbond_batch::*;
 pub use crate::era_update_active::*;
+pub use crate::era_update_active_batch::*;
 pub use crate::era_update_rate::*;
+pub use crate::era_update_rate_override::*;
 pub use crate::era_withdraw::*;
 pub use crate::errors::Errors;
 pub use crate::initialize::*;
+pub use crate::rebalance::*;
+pub use crate::rebalance_reserve::*;
 pub use crate::redelegate::*;
+pub use crate::referral::*;
+pub use crate::staker_cancel_unstake::*;
+pub use crate::staker_deposit_stake_account::*;
+pub use crate::staker_instant_unstake::*;
 pub use crate::staker_stake::*;
+pub use crate::staker_transfer_unstake_ticket::*;
 pub use crate::staker_unstake::*;
 pub use crate::staker_withdraw::*;
+pub use crate::staker_withdraw_batch::*;
+pub use crate::staker_withdraw_stake::*;
 pub use crate::states::*;
+pub use crate::vote_account::*;
 
 declare_id!("EiweRuKbjvwkRhraQ5vreVd3xon3JYfB7efiPDhFuVeL");
 
-fn check_context<T: Bumps>(ctx: &Context<T>) -> Result<()> {
+fn check_program_id<T: Bumps>(ctx: &Context<T>) -> Result<()> {
     if !check_id(ctx.program_id) {
         return err!(Errors::ProgramIdNotMatch);
     }
 
+    Ok(())
+}
+
+fn check_context<T: Bumps>(ctx: &Context<T>) -> Result<()> {
+    check_program_id(ctx)?;
+
     if !ctx.remaining_accounts.is_empty() {
         return err!(Errors::RemainingAccountsNotMatch);
     }
@@ -115,6 +155,62 @@ pub mod stake_manager_program {
         Ok(())
     }
 
+    pub fn set_cancel_unstake_fee_refund(
+        ctx: Context<SetCancelUnstakeFeeRefund>,
+        fee_refund: bool,
+    ) -> Result<()> {
+        check_context(&ctx)?;
+
+        ctx.accounts.process(fee_refund)?;
+
+        Ok(())
+    }
+
+    pub fn set_referral_fee_share(
+        ctx: Context<SetReferralFeeShare>,
+        referral_fee_share: u64,
+    ) -> Result<()> {
+        check_context(&ctx)?;
+
+        ctx.accounts.process(referral_fee_share)?;
+
+        Ok(())
+    }
+
+    pub fn set_crank_tip(
+        ctx: Context<SetCrankTip>,
+        crank_tip: u64,
+        crank_tip_era_budget: u64,
+    ) -> Result<()> {
+        check_context(&ctx)?;
+
+        ctx.accounts.process(crank_tip, crank_tip_era_budget)?;
+
+        Ok(())
+    }
+
+    pub fn set_max_validator_commission(
+        ctx: Context<SetMaxValidatorCommission>,
+        max_validator_commission: u8,
+    ) -> Result<()> {
+        check_context(&ctx)?;
+
+        ctx.accounts.process(max_validator_commission)?;
+
+        Ok(())
+    }
+
+    pub fn set_min_rebalance_amount(
+        ctx: Context<SetMinRebalanceAmount>,
+        min_rebalance_amount: u64,
+    ) -> Result<()> {
+        check_context(&ctx)?;
+
+        ctx.accounts.process(min_rebalance_amount)?;
+
+        Ok(())
+    }
+
     pub fn set_rate_change_limit(
         ctx: Context<SetRateChangeLimit>,
         rate_change_limit: u64,
@@ -126,10 +222,32 @@ pub mod stake_manager_program {
         Ok(())
     }
 
-    pub fn add_validator(ctx: Context<AddValidator>, new_validator: Pubkey) -> Result<()> {
+    pub fn set_reserve_target(ctx: Context<SetReserveTarget>, reserve_target: u64) -> Result<()> {
         check_context(&ctx)?;
 
-        ctx.accounts.process(new_validator)?;
+        ctx.accounts
+            .process(reserve_target, ctx.bumps.reserve_pool)?;
+
+        Ok(())
+    }
+
+    pub fn set_instant_unstake_fee_commission(
+        ctx: Context<SetInstantUnstakeFeeCommission>,
+        min_fee_commission: u64,
+        max_fee_commission: u64,
+    ) -> Result<()> {
+        check_context(&ctx)?;
+
+        ctx.accounts
+            .process(min_fee_commission, max_fee_commission)?;
+
+        Ok(())
+    }
+
+    pub fn add_validator(ctx: Context<AddValidator>) -> Result<()> {
+        check_context(&ctx)?;
+
+        ctx.accounts.process()?;
 
         Ok(())
     }
@@ -142,6 +260,34 @@ pub mod stake_manager_program {
         Ok(())
     }
 
+    pub fn set_validator_weight(
+        ctx: Context<SetValidatorWeight>,
+        validator: Pubkey,
+        weight: u64,
+    ) -> Result<()> {
+        check_context(&ctx)?;
+
+        ctx.accounts.process(validator, weight)?;
+
+        Ok(())
+    }
+
+    pub fn initialize_validator_registry(ctx: Context<InitializeValidatorRegistry>) -> Result<()> {
+        check_context(&ctx)?;
+
+        ctx.accounts.process()?;
+
+        Ok(())
+    }
+
+    pub fn initialize_era_history(ctx: Context<InitializeEraHistory>) -> Result<()> {
+        check_context(&ctx)?;
+
+        ctx.accounts.process()?;
+
+        Ok(())
+    }
+
     pub fn realloc_stake_manager(ctx: Context<ReallocStakeManager>, new_size: u32) -> Result<()> {
         check_context(&ctx)?;
 
@@ -150,7 +296,18 @@ pub mod stake_manager_program {
         Ok(())
     }
 
-    pub fn upgrade_stake_manager(ctx: Context<UpgradeStakeManager>) -> Result<()> {
+    pub fn upgrade_stake_manager(
+        ctx: Context<UpgradeStakeManager>,
+        total_unstake_unclaimed: u64,
+    ) -> Result<()> {
+        check_context(&ctx)?;
+
+        ctx.accounts.process(total_unstake_unclaimed)?;
+
+        Ok(())
+    }
+
+    pub fn upgrade_unstake_account(ctx: Context<UpgradeUnstakeAccount>) -> Result<()> {
         check_context(&ctx)?;
 
         ctx.accounts.process()?;
@@ -168,12 +325,97 @@ pub mod stake_manager_program {
         Ok(())
     }
 
+    pub fn rebalance(ctx: Context<Rebalance>, rebalance_amount: u64) -> Result<()> {
+        check_context(&ctx)?;
+
+        ctx.accounts.process(rebalance_amount)?;
+
+        Ok(())
+    }
+
+    // referral
+
+    pub fn create_referral_account(ctx: Context<CreateReferralAccount>) -> Result<()> {
+        check_context(&ctx)?;
+
+        ctx.accounts.process()?;
+
+        Ok(())
+    }
+
+    pub fn claim_referral_reward(ctx: Context<ClaimReferralReward>) -> Result<()> {
+        check_context(&ctx)?;
+
+        ctx.accounts.process()?;
+
+        Ok(())
+    }
+
+    pub fn sync_referred_stake(ctx: Context<SyncReferredStake>) -> Result<()> {
+        check_context(&ctx)?;
+
+        ctx.accounts.process()?;
+
+        Ok(())
+    }
+
+    // delinquent
+    pub fn deactivate_delinquent(ctx: Context<DeactivateDelinquent>) -> Result<()> {
+        check_context(&ctx)?;
+
+        ctx.accounts.process()?;
+
+        Ok(())
+    }
+
+    pub fn reactivate_delinquent(ctx: Context<ReactivateDelinquent>) -> Result<()> {
+        check_context(&ctx)?;
+
+        ctx.accounts.process()?;
+
+        Ok(())
+    }
+
+    // reserve
+
+    pub fn rebalance_reserve(ctx: Context<RebalanceReserve>) -> Result<()> {
+        check_context(&ctx)?;
+
+        ctx.accounts.process()?;
+
+        Ok(())
+    }
+
     // staker
 
     pub fn stake(ctx: Context<Stake>, stake_amount: u64) -> Result<()> {
         check_context(&ctx)?;
 
-        ctx.accounts.process(stake_amount)?;
+        ctx.accounts.process(stake_amount, 0)?;
+
+        Ok(())
+    }
+
+    pub fn stake_with_min_out(
+        ctx: Context<Stake>,
+        stake_amount: u64,
+        min_rsol_out: u64,
+    ) -> Result<()> {
+        check_context(&ctx)?;
+
+        ctx.accounts.process(stake_amount, min_rsol_out)?;
+
+        Ok(())
+    }
+
+    pub fn stake_with_referrer(
+        ctx: Context<StakeWithReferrer>,
+        stake_amount: u64,
+        min_rsol_out: u64,
+    ) -> Result<()> {
+        check_context(&ctx)?;
+
+        ctx.accounts.process(stake_amount, min_rsol_out)?;
 
         Ok(())
     }
@@ -181,7 +423,27 @@ pub mod stake_manager_program {
     pub fn unstake(ctx: Context<Unstake>, unstake_amount: u64) -> Result<()> {
         check_context(&ctx)?;
 
-        ctx.accounts.process(unstake_amount)?;
+        ctx.accounts.process(unstake_amount, 0)?;
+
+        Ok(())
+    }
+
+    pub fn unstake_with_min_out(
+        ctx: Context<Unstake>,
+        unstake_amount: u64,
+        min_sol_out: u64,
+    ) -> Result<()> {
+        check_context(&ctx)?;
+
+        ctx.accounts.process(unstake_amount, min_sol_out)?;
+
+        Ok(())
+    }
+
+    pub fn cancel_unstake(ctx: Context<CancelUnstake>) -> Result<()> {
+        check_context(&ctx)?;
+
+        ctx.accounts.process()?;
 
         Ok(())
     }
@@ -189,21 +451,100 @@ pub mod stake_manager_program {
     pub fn withdraw(ctx: Context<Withdraw>) -> Result<()> {
         check_context(&ctx)?;
 
+        ctx.accounts.process(false)?;
+
+        Ok(())
+    }
+
+    pub fn withdraw_partial(ctx: Context<Withdraw>) -> Result<()> {
+        check_context(&ctx)?;
+
+        ctx.accounts.process(true)?;
+
+        Ok(())
+    }
+
+    pub fn withdraw_batch<'info>(
+        ctx: Context<'_, '_, 'info, 'info, WithdrawBatch<'info>>,
+    ) -> Result<()> {
+        check_program_id(&ctx)?;
+
+        ctx.accounts.process(ctx.remaining_accounts)?;
+
+        Ok(())
+    }
+
+    pub fn transfer_unstake_ticket(
+        ctx: Context<TransferUnstakeTicket>,
+        new_recipient: Pubkey,
+    ) -> Result<()> {
+        check_context(&ctx)?;
+
+        ctx.accounts.process(new_recipient)?;
+
+        Ok(())
+    }
+
+    pub fn accept_unstake_ticket(ctx: Context<AcceptUnstakeTicket>) -> Result<()> {
+        check_context(&ctx)?;
+
+        ctx.accounts.process()?;
+
+        Ok(())
+    }
+
+    pub fn deposit_stake_account(ctx: Context<DepositStakeAccount>) -> Result<()> {
+        check_context(&ctx)?;
+
         ctx.accounts.process()?;
 
         Ok(())
     }
 
+    pub fn instant_unstake(
+        ctx: Context<InstantUnstake>,
+        unstake_amount: u64,
+        min_sol_out: u64,
+    ) -> Result<()> {
+        check_context(&ctx)?;
+
+        ctx.accounts.process(unstake_amount, min_sol_out)?;
+
+        Ok(())
+    }
+
+    pub fn withdraw_stake(ctx: Context<WithdrawStake>, unstake_amount: u64) -> Result<()> {
+        check_context(&ctx)?;
+
+        ctx.accounts.process(unstake_amount)?;
+
+        Ok(())
+    }
+
     // era
 
     pub fn era_new(ctx: Context<EraNew>) -> Result<()> {
         check_context(&ctx)?;
 
-        ctx.accounts.process()?;
+        ctx.accounts.process(false)?;
 
         Ok(())
     }
 
+    pub fn era_new_catch_up(ctx: Context<EraNew>) -> Result<()> {
+        check_context(&ctx)?;
+
+        ctx.accounts.process(true)?;
+
+        Ok(())
+    }
+
+    pub fn era_status(ctx: Context<EraStatus>) -> Result<EraStatusData> {
+        check_context(&ctx)?;
+
+        ctx.accounts.process()
+    }
+
     pub fn era_bond(ctx: Context<EraBond>) -> Result<()> {
         check_context(&ctx)?;
 
@@ -212,6 +553,14 @@ pub mod stake_manager_program {
         Ok(())
     }
 
+    pub fn era_skip_validator(ctx: Context<EraSkipValidator>) -> Result<()> {
+        check_context(&ctx)?;
+
+        ctx.accounts.process()?;
+
+        Ok(())
+    }
+
     pub fn era_unbond(ctx: Context<EraUnbond>) -> Result<()> {
         check_context(&ctx)?;
 
@@ -220,6 +569,18 @@ pub mod stake_manager_program {
         Ok(())
     }
 
+    pub fn era_unbond_batch<'info>(
+        ctx: Context<'_, '_, 'info, 'info, EraUnbondBatch<'info>>,
+        stake_accounts_len: u8,
+    ) -> Result<()> {
+        check_program_id(&ctx)?;
+
+        ctx.accounts
+            .process(ctx.remaining_accounts, stake_accounts_len)?;
+
+        Ok(())
+    }
+
     pub fn era_update_active(ctx: Context<EraUpdateActive>) -> Result<()> {
         check_context(&ctx)?;
 
@@ -228,10 +589,32 @@ pub mod stake_manager_program {
         Ok(())
     }
 
+    pub fn era_update_active_batch<'info>(
+        ctx: Context<'_, '_, 'info, 'info, EraUpdateActiveBatch<'info>>,
+    ) -> Result<()> {
+        check_program_id(&ctx)?;
+
+        ctx.accounts.process(ctx.remaining_accounts)?;
+
+        Ok(())
+    }
+
     pub fn era_update_rate(ctx: Context<EraUpdateRate>) -> Result<()> {
         check_context(&ctx)?;
 
-        ctx.accounts.process()?;
+        ctx.accounts.process(None)?;
+
+        Ok(())
+    }
+
+    pub fn era_update_rate_override(
+        ctx: Context<EraUpdateRateOverride>,
+        min_rate: u64,
+        max_rate: u64,
+    ) -> Result<()> {
+        check_context(&ctx)?;
+
+        ctx.accounts.process(min_rate, max_rate)?;
 
         Ok(())
     }
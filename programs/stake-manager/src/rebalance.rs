@@ -0,0 +1,133 @@
+use crate::{Errors, SplitRedelegate, StakeManager, ValidatorRegistry};
+use anchor_lang::prelude::*;
+use anchor_lang::{
+    solana_program::{
+        stake::{self, state::StakeStateV2},
+        sysvar::stake_history,
+    },
+    system_program,
+};
+use anchor_spl::stake::{Stake, StakeAccount};
+
+#[derive(Accounts)]
+pub struct Rebalance<'info> {
+    #[account(
+        mut,
+        constraint = stake_manager.is_upgraded() @ Errors::StakeManagerNotUpgraded
+    )]
+    pub stake_manager: Box<Account<'info, StakeManager>>,
+
+    #[account(
+        mut,
+        address = stake_manager.validator_registry @ Errors::ValidatorRegistryNotMatch
+    )]
+    pub validator_registry: Box<Account<'info, ValidatorRegistry>>,
+
+    /// CHECK: validator account
+    #[account(mut)]
+    pub to_validator: UncheckedAccount<'info>,
+
+    #[account(
+        seeds = [
+            &stake_manager.key().to_bytes(),
+            StakeManager::POOL_SEED
+        ],
+        bump = stake_manager.pool_seed_bump
+    )]
+    pub stake_pool: SystemAccount<'info>,
+
+    #[account(mut)]
+    pub from_stake_account: Account<'info, StakeAccount>,
+
+    #[account(
+        init,
+        payer = rent_payer,
+        space = std::mem::size_of::<StakeStateV2>(),
+        owner = stake::program::ID,
+    )]
+    pub split_stake_account: Account<'info, StakeAccount>,
+
+    #[account(
+        init,
+        payer = rent_payer,
+        space = std::mem::size_of::<StakeStateV2>(),
+        owner = stake::program::ID,
+    )]
+    pub to_stake_account: Account<'info, StakeAccount>,
+
+    #[account(
+        mut,
+        owner = system_program::ID
+    )]
+    pub rent_payer: Signer<'info>,
+
+    pub clock: Sysvar<'info, Clock>,
+    /// CHECK: stake config account
+    #[account(address = stake::config::ID)]
+    pub stake_config: UncheckedAccount<'info>,
+    /// CHECK: stake history
+    #[account(address = stake_history::ID)]
+    pub stake_history: UncheckedAccount<'info>,
+    pub stake_program: Program<'info, Stake>,
+    pub system_program: Program<'info, System>,
+}
+
+#[event]
+pub struct EventRebalance {
+    pub from_validator: Pubkey,
+    pub to_validator: Pubkey,
+    pub from_stake_account: Pubkey,
+    pub to_stake_account: Pubkey,
+    pub rebalance_amount: u64,
+    pub rebalance_limit: u64,
+}
+
+impl<'info> Rebalance<'info> {
+    // permissionless redelegation toward the validators' target weights
+    pub fn process(&mut self, rebalance_amount: u64) -> Result<()> {
+        // every rebalance leaves a split account behind, so dust moves are not worth it
+        require_gte!(
+            rebalance_amount,
+            self.stake_manager.min_rebalance_amount,
+            Errors::RebalanceAmountTooLow
+        );
+
+        let mut split_redelegate = SplitRedelegate {
+            stake_manager: &mut self.stake_manager,
+            validator_registry: &mut self.validator_registry,
+            to_validator: &self.to_validator,
+            stake_pool: &self.stake_pool,
+            from_stake_account: &self.from_stake_account,
+            split_stake_account: &self.split_stake_account,
+            to_stake_account: &self.to_stake_account,
+            rent_payer: &self.rent_payer,
+            clock: &self.clock,
+            stake_config: &self.stake_config,
+            stake_history: &self.stake_history,
+            stake_program: &self.stake_program,
+        };
+        let delegation = split_redelegate.check(rebalance_amount)?;
+
+        let rebalance_limit = split_redelegate
+            .validator_registry
+            .calc_rebalance_limit(&delegation.voter_pubkey, self.to_validator.key)?;
+        require_gte!(
+            rebalance_limit,
+            rebalance_amount,
+            Errors::RebalanceAmountOverLimit
+        );
+
+        split_redelegate.process(&delegation, rebalance_amount)?;
+
+        emit!(EventRebalance {
+            from_validator: delegation.voter_pubkey,
+            to_validator: self.to_validator.key(),
+            from_stake_account: self.from_stake_account.key(),
+            to_stake_account: self.to_stake_account.key(),
+            rebalance_amount,
+            rebalance_limit
+        });
+
+        Ok(())
+    }
+}
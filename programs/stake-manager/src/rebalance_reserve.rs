@@ -0,0 +1,115 @@
+use crate::{Errors, StakeManager};
+use anchor_lang::prelude::*;
+use anchor_lang::system_program::{transfer, Transfer};
+
+#[derive(Accounts)]
+pub struct RebalanceReserve<'info> {
+    #[account(
+        mut,
+        constraint = stake_manager.is_upgraded() @ Errors::StakeManagerNotUpgraded
+    )]
+    pub stake_manager: Box<Account<'info, StakeManager>>,
+
+    #[account(
+        mut,
+        seeds = [
+            &stake_manager.key().to_bytes(),
+            StakeManager::POOL_SEED,
+        ],
+        bump = stake_manager.pool_seed_bump
+    )]
+    pub stake_pool: SystemAccount<'info>,
+
+    #[account(
+        mut,
+        seeds = [
+            &stake_manager.key().to_bytes(),
+            StakeManager::RESERVE_SEED,
+        ],
+        bump = stake_manager.reserve_seed_bump
+    )]
+    pub reserve_pool: SystemAccount<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[event]
+pub struct EventRebalanceReserve {
+    pub era: u64,
+    pub to_reserve: bool,
+    pub amount: u64,
+    pub reserve_balance: u64,
+    pub reserve_utilization: u64,
+}
+
+impl<'info> RebalanceReserve<'info> {
+    pub fn process(&mut self) -> Result<()> {
+        let reserve_target = self.stake_manager.reserve_target;
+        let reserve_balance = self.stake_manager.reserve_balance;
+
+        // only lamports staked in the current era and not yet bonded can fill the reserve,
+        // fills and releases are netted against era bond and unbond at era_new
+        let (to_reserve, amount) = if reserve_balance < reserve_target {
+            (
+                true,
+                (reserve_target - reserve_balance).min(self.stake_manager.calc_era_pool_bond()),
+            )
+        } else {
+            (false, reserve_balance - reserve_target)
+        };
+        require_gt!(amount, 0, Errors::ReserveNoNeedRebalance);
+
+        if to_reserve {
+            transfer(
+                CpiContext::new_with_signer(
+                    self.system_program.to_account_info(),
+                    Transfer {
+                        from: self.stake_pool.to_account_info(),
+                        to: self.reserve_pool.to_account_info(),
+                    },
+                    &[&[
+                        &self.stake_manager.key().to_bytes(),
+                        StakeManager::POOL_SEED,
+                        &[self.stake_manager.pool_seed_bump],
+                    ]],
+                ),
+                amount,
+            )?;
+
+            self.stake_manager.era_reserve_fill += amount;
+            self.stake_manager.reserve_balance += amount;
+        } else {
+            transfer(
+                CpiContext::new_with_signer(
+                    self.system_program.to_account_info(),
+                    Transfer {
+                        from: self.reserve_pool.to_account_info(),
+                        to: self.stake_pool.to_account_info(),
+                    },
+                    &[&[
+                        &self.stake_manager.key().to_bytes(),
+                        StakeManager::RESERVE_SEED,
+                        &[self.stake_manager.reserve_seed_bump],
+                    ]],
+                ),
+                amount,
+            )?;
+
+            self.stake_manager.era_reserve_release += amount;
+            self.stake_manager.reserve_balance -= amount;
+        }
+
+        let reserve_utilization = self
+            .stake_manager
+            .calc_reserve_utilization(self.stake_manager.reserve_balance)?;
+
+        emit!(EventRebalanceReserve {
+            era: self.stake_manager.latest_era,
+            to_reserve,
+            amount,
+            reserve_balance: self.stake_manager.reserve_balance,
+            reserve_utilization
+        });
+        Ok(())
+    }
+}
@@ -1,9 +1,12 @@
-use crate::{Errors, StakeManager};
+use crate::{EraPhase, Errors, StakeManager, ValidatorRegistry, VoteAccountState};
 use anchor_lang::prelude::*;
 use anchor_lang::{
     solana_program::{
         program::invoke_signed,
-        stake::{self, state::StakeStateV2},
+        stake::{
+            self,
+            state::{Delegation, StakeStateV2},
+        },
         sysvar::stake_history,
     },
     system_program,
@@ -15,10 +18,17 @@ use anchor_spl::stake::{withdraw, Stake, StakeAccount, Withdraw};
 pub struct Redelegate<'info> {
     #[account(
         mut, 
-        has_one = balancer @ Errors::BalancerNotMatch
+        has_one = balancer @ Errors::BalancerNotMatch,
+        constraint = stake_manager.is_upgraded() @ Errors::StakeManagerNotUpgraded
     )]
     pub stake_manager: Box<Account<'info, StakeManager>>,
 
+    #[account(
+        mut,
+        address = stake_manager.validator_registry @ Errors::ValidatorRegistryNotMatch
+    )]
+    pub validator_registry: Box<Account<'info, ValidatorRegistry>>,
+
     pub balancer: Signer<'info>,
 
     /// CHECK: validator account
@@ -79,14 +89,31 @@ pub struct EventRedelegate {
 }
 
 
-impl<'info> Redelegate<'info> {
-    pub fn process(&mut self, redelegate_amount: u64) -> Result<()> {
-        require_gt!(redelegate_amount, 0, Errors::AmountUnmatch);
+// the split and redelegation shared by redelegate and rebalance
+pub struct SplitRedelegate<'a, 'info> {
+    pub stake_manager: &'a mut Account<'info, StakeManager>,
+    pub validator_registry: &'a mut Account<'info, ValidatorRegistry>,
+    pub to_validator: &'a UncheckedAccount<'info>,
+    pub stake_pool: &'a SystemAccount<'info>,
+    pub from_stake_account: &'a Account<'info, StakeAccount>,
+    pub split_stake_account: &'a Account<'info, StakeAccount>,
+    pub to_stake_account: &'a Account<'info, StakeAccount>,
+    pub rent_payer: &'a Signer<'info>,
+    pub clock: &'a Sysvar<'info, Clock>,
+    pub stake_config: &'a UncheckedAccount<'info>,
+    pub stake_history: &'a UncheckedAccount<'info>,
+    pub stake_program: &'a Program<'info, Stake>,
+}
+
+impl<'a, 'info> SplitRedelegate<'a, 'info> {
+    // returns the source delegation once the move is valid
+    pub fn check(&self, amount: u64) -> Result<Delegation> {
+        require_gt!(amount, 0, Errors::AmountUnmatch);
 
         require!(
-            self.stake_manager.era_process_data.is_empty(), 
-            Errors::EraIsProcessing)
-        ;
+            self.stake_manager.era_phase == EraPhase::Idle,
+            Errors::EraIsProcessing
+        );
 
         require!(
             self.stake_manager
@@ -118,6 +145,9 @@ impl<'info> Redelegate<'info> {
             Errors::ValidatorNotExist
         );
 
+        VoteAccountState::parse(self.to_validator)?
+            .check_commission(self.stake_manager.max_validator_commission)?;
+
         let delegation = self
             .from_stake_account
             .delegation()
@@ -126,20 +156,43 @@ impl<'info> Redelegate<'info> {
         // require stake is active (deactivation_epoch == u64::MAX)
         require_eq!(
             delegation.deactivation_epoch,
-            std::u64::MAX,
+            u64::MAX,
             Errors::StakeAccountNotActive
         );
 
-        require_keys_neq!(self.to_validator.key(), delegation.voter_pubkey, Errors::ValidatorNotMatch);
+        require_keys_neq!(
+            self.to_validator.key(),
+            delegation.voter_pubkey,
+            Errors::ValidatorNotMatch
+        );
+
+        require_gte!(delegation.stake, amount, Errors::AmountUnmatch);
+
+        // a partial move keeps the source account, a full one replaces it
+        if amount < delegation.stake {
+            require_gt!(
+                self.stake_manager.stake_accounts_len_limit,
+                self.stake_manager.stake_accounts.len() as u64,
+                Errors::StakeAccountsLenOverLimit
+            );
+        }
+
+        require_gt!(
+            self.stake_manager.split_accounts_len_limit,
+            self.stake_manager.split_accounts.len() as u64,
+            Errors::SplitAccountsLenOverLimit
+        );
 
-        require_gte!(delegation.stake, redelegate_amount, Errors::AmountUnmatch);
+        Ok(delegation)
+    }
 
-        let will_redelegate_from_stake_account =  if redelegate_amount < delegation.stake {
+    pub fn process(&mut self, delegation: &Delegation, amount: u64) -> Result<()> {
+        let will_redelegate_from_stake_account = if amount < delegation.stake {
             // split
             let split_instruction = stake::instruction::split(
                 self.from_stake_account.to_account_info().key,
                 self.stake_pool.key,
-                redelegate_amount,
+                amount,
                 &self.split_stake_account.key(),
             )
             .last()
@@ -182,6 +235,8 @@ impl<'info> Redelegate<'info> {
             self.stake_manager
                 .stake_accounts
                 .retain(|&e| e != self.from_stake_account.key());
+            self.validator_registry
+                .remove_stake_account(&delegation.voter_pubkey)?;
 
             self.from_stake_account.to_account_info()
         };
@@ -199,7 +254,7 @@ impl<'info> Redelegate<'info> {
 
         invoke_signed(
             redelegate_instruction,
-                &[
+            &[
                 self.stake_program.to_account_info(),
                 will_redelegate_from_stake_account.clone(),
                 self.to_stake_account.to_account_info(),
@@ -214,6 +269,12 @@ impl<'info> Redelegate<'info> {
             ]],
         )?;
 
+        self.validator_registry
+            .add_deactivating_stake(&delegation.voter_pubkey, amount)?;
+        self.validator_registry
+            .add_activating_stake(self.to_validator.key, amount)?;
+        self.validator_registry
+            .add_stake_account(self.to_validator.key)?;
 
         self.stake_manager
             .split_accounts
@@ -222,6 +283,28 @@ impl<'info> Redelegate<'info> {
         self.stake_manager
             .stake_accounts
             .push(self.to_stake_account.key());
+        Ok(())
+    }
+}
+
+impl<'info> Redelegate<'info> {
+    pub fn process(&mut self, redelegate_amount: u64) -> Result<()> {
+        let mut split_redelegate = SplitRedelegate {
+            stake_manager: &mut self.stake_manager,
+            validator_registry: &mut self.validator_registry,
+            to_validator: &self.to_validator,
+            stake_pool: &self.stake_pool,
+            from_stake_account: &self.from_stake_account,
+            split_stake_account: &self.split_stake_account,
+            to_stake_account: &self.to_stake_account,
+            rent_payer: &self.rent_payer,
+            clock: &self.clock,
+            stake_config: &self.stake_config,
+            stake_history: &self.stake_history,
+            stake_program: &self.stake_program,
+        };
+        let delegation = split_redelegate.check(redelegate_amount)?;
+        split_redelegate.process(&delegation, redelegate_amount)?;
 
         emit!(EventRedelegate{ 
             from_stake_account: self.from_stake_account.key(), 
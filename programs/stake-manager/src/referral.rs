@@ -0,0 +1,309 @@
+use crate::staker_stake::*;
+use crate::{Errors, ReferralAccount, ReferredStake, StakeManager};
+use anchor_lang::{prelude::*, system_program};
+use anchor_spl::token::{Mint, Token, TokenAccount};
+
+use mint_manager_program::cpi::accounts::MintToken;
+use mint_manager_program::program::MintManagerProgram;
+use mint_manager_program::{self, MintManager};
+
+#[derive(Accounts)]
+pub struct CreateReferralAccount<'info> {
+    #[account(constraint = stake_manager.is_upgraded() @ Errors::StakeManagerNotUpgraded)]
+    pub stake_manager: Box<Account<'info, StakeManager>>,
+
+    #[account(
+        init,
+        payer = referrer,
+        space = 8 + ReferralAccount::INIT_SPACE,
+        seeds = [
+            &stake_manager.key().to_bytes(),
+            &referrer.key().to_bytes(),
+            ReferralAccount::SEED,
+        ],
+        bump,
+    )]
+    pub referral_account: Box<Account<'info, ReferralAccount>>,
+
+    #[account(
+        mut,
+        owner = system_program::ID
+    )]
+    pub referrer: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> CreateReferralAccount<'info> {
+    pub fn process(&mut self) -> Result<()> {
+        self.referral_account.set_inner(ReferralAccount {
+            stake_manager: self.stake_manager.key(),
+            referrer: self.referrer.key(),
+            total_sol_referred: 0,
+            total_rsol_minted: 0,
+            referred_rsol: 0,
+            reward_per_rsol_checkpoint: self.stake_manager.referral_reward_per_rsol,
+            pending_reward: 0,
+            total_reward_claimed: 0,
+        });
+
+        msg!("CreateReferralAccount: referrer: {}", self.referrer.key());
+        Ok(())
+    }
+}
+
+#[derive(Accounts)]
+pub struct StakeWithReferrer<'info> {
+    pub stake: Stake<'info>,
+
+    #[account(
+        mut,
+        seeds = [
+            &stake.stake_manager.key().to_bytes(),
+            &referral_account.referrer.to_bytes(),
+            ReferralAccount::SEED,
+        ],
+        bump
+    )]
+    pub referral_account: Box<Account<'info, ReferralAccount>>,
+
+    #[account(
+        init_if_needed,
+        payer = rent_payer,
+        space = 8 + ReferredStake::INIT_SPACE,
+        seeds = [
+            &stake.stake_manager.key().to_bytes(),
+            &stake.mint_to.key().to_bytes(),
+            ReferredStake::SEED,
+        ],
+        bump
+    )]
+    pub referred_stake: Box<Account<'info, ReferredStake>>,
+
+    #[account(
+        mut,
+        owner = system_program::ID
+    )]
+    pub rent_payer: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[event]
+pub struct EventStakeWithReferrer {
+    pub era: u64,
+    pub staker: Pubkey,
+    pub referrer: Pubkey,
+    pub stake_amount: u64,
+    pub rsol_amount: u64,
+}
+
+impl<'info> StakeWithReferrer<'info> {
+    pub fn process(&mut self, stake_amount: u64, min_rsol_out: u64) -> Result<()> {
+        require_keys_neq!(
+            self.referral_account.referrer,
+            self.stake.from.key(),
+            Errors::SelfReferral
+        );
+
+        // first stake with a referrer initializes the token account's referred stake
+        if self.referred_stake.token_account == Pubkey::default() {
+            self.referred_stake.stake_manager = self.stake.stake_manager.key();
+            self.referred_stake.token_account = self.stake.mint_to.key();
+            self.referred_stake.referrer = self.referral_account.referrer;
+        }
+
+        // another referrer can only take over once the previous one's rsol is released
+        if self.referred_stake.referrer != self.referral_account.referrer {
+            require_eq!(self.referred_stake.rsol_amount, 0, Errors::ReferrerNotMatch);
+            self.referred_stake.referrer = self.referral_account.referrer;
+        }
+
+        // rsol moved out of the token account since the last stake is no longer referred
+        self.referred_stake.release_over_balance(
+            self.stake.mint_to.amount,
+            &mut self.referral_account,
+            &mut self.stake.stake_manager,
+        )?;
+
+        let rsol_amount = self.stake.process(stake_amount, min_rsol_out)?;
+
+        self.referral_account
+            .settle(self.stake.stake_manager.referral_reward_per_rsol)?;
+        self.referral_account.total_sol_referred += stake_amount;
+        self.referral_account.total_rsol_minted += rsol_amount;
+        self.referral_account.referred_rsol += rsol_amount;
+        self.referred_stake.rsol_amount += rsol_amount;
+        self.stake.stake_manager.total_referred_rsol += rsol_amount;
+
+        emit!(EventStakeWithReferrer {
+            era: self.stake.stake_manager.latest_era,
+            staker: self.stake.from.key(),
+            referrer: self.referral_account.referrer,
+            stake_amount,
+            rsol_amount
+        });
+        Ok(())
+    }
+}
+
+#[derive(Accounts)]
+pub struct ClaimReferralReward<'info> {
+    #[account(
+        has_one = rsol_mint @ Errors::MintAccountNotMatch,
+        constraint = stake_manager.is_upgraded() @ Errors::StakeManagerNotUpgraded
+    )]
+    pub stake_manager: Box<Account<'info, StakeManager>>,
+
+    #[account(
+        seeds = [
+            &stake_manager.key().to_bytes(),
+            StakeManager::POOL_SEED,
+        ],
+        bump = stake_manager.pool_seed_bump
+    )]
+    pub stake_pool: SystemAccount<'info>,
+
+    #[account(
+        mut,
+        seeds = [
+            &stake_manager.key().to_bytes(),
+            &referrer.key().to_bytes(),
+            ReferralAccount::SEED,
+        ],
+        bump,
+        has_one = referrer @ Errors::ReferrerNotMatch
+    )]
+    pub referral_account: Box<Account<'info, ReferralAccount>>,
+
+    pub referrer: Signer<'info>,
+
+    #[account(
+        has_one = rsol_mint @ Errors::MintAccountNotMatch
+    )]
+    pub mint_manager: Box<Account<'info, MintManager>>,
+
+    #[account(mut)]
+    pub rsol_mint: Box<Account<'info, Mint>>,
+
+    #[account(
+        mut,
+        token::mint = stake_manager.rsol_mint
+    )]
+    pub mint_to: Box<Account<'info, TokenAccount>>,
+
+    /// CHECK:  check on mint manager program
+    pub mint_authority: UncheckedAccount<'info>,
+
+    pub mint_manager_program: Program<'info, MintManagerProgram>,
+    pub token_program: Program<'info, Token>,
+}
+
+#[event]
+pub struct EventClaimReferralReward {
+    pub era: u64,
+    pub referrer: Pubkey,
+    pub mint_to: Pubkey,
+    pub reward_amount: u64,
+}
+
+impl<'info> ClaimReferralReward<'info> {
+    pub fn process(&mut self) -> Result<()> {
+        self.referral_account
+            .settle(self.stake_manager.referral_reward_per_rsol)?;
+
+        let reward_amount = self.referral_account.pending_reward;
+        require_gt!(reward_amount, 0, Errors::ReferralRewardIsZero);
+
+        // already counted in total_rsol_supply when the reward was accrued
+        let cpi_program = self.mint_manager_program.to_account_info();
+        let cpi_accounts = MintToken {
+            mint_manager: self.mint_manager.to_account_info(),
+            rsol_mint: self.rsol_mint.to_account_info(),
+            mint_to: self.mint_to.to_account_info(),
+            mint_authority: self.mint_authority.to_account_info(),
+            ext_mint_authority: self.stake_pool.to_account_info(),
+            token_program: self.token_program.to_account_info(),
+        };
+        mint_manager_program::cpi::mint_token(
+            CpiContext::new(cpi_program, cpi_accounts).with_signer(&[&[
+                &self.stake_manager.key().to_bytes(),
+                StakeManager::POOL_SEED,
+                &[self.stake_manager.pool_seed_bump],
+            ]]),
+            reward_amount,
+        )?;
+
+        self.referral_account.pending_reward = 0;
+        self.referral_account.total_reward_claimed += reward_amount;
+
+        emit!(EventClaimReferralReward {
+            era: self.stake_manager.latest_era,
+            referrer: self.referrer.key(),
+            mint_to: self.mint_to.key(),
+            reward_amount
+        });
+        Ok(())
+    }
+}
+
+#[derive(Accounts)]
+pub struct SyncReferredStake<'info> {
+    #[account(
+        mut,
+        constraint = stake_manager.is_upgraded() @ Errors::StakeManagerNotUpgraded
+    )]
+    pub stake_manager: Box<Account<'info, StakeManager>>,
+
+    #[account(token::mint = stake_manager.rsol_mint)]
+    pub token_account: Box<Account<'info, TokenAccount>>,
+
+    #[account(
+        mut,
+        seeds = [
+            &stake_manager.key().to_bytes(),
+            &token_account.key().to_bytes(),
+            ReferredStake::SEED,
+        ],
+        bump
+    )]
+    pub referred_stake: Box<Account<'info, ReferredStake>>,
+
+    #[account(
+        mut,
+        seeds = [
+            &stake_manager.key().to_bytes(),
+            &referred_stake.referrer.to_bytes(),
+            ReferralAccount::SEED,
+        ],
+        bump
+    )]
+    pub referral_account: Box<Account<'info, ReferralAccount>>,
+}
+
+#[event]
+pub struct EventSyncReferredStake {
+    pub era: u64,
+    pub token_account: Pubkey,
+    pub referrer: Pubkey,
+    pub rsol_amount: u64,
+}
+
+impl<'info> SyncReferredStake<'info> {
+    // permissionless, releases referred rsol transferred out of the token account
+    pub fn process(&mut self) -> Result<()> {
+        self.referred_stake.release_over_balance(
+            self.token_account.amount,
+            &mut self.referral_account,
+            &mut self.stake_manager,
+        )?;
+
+        emit!(EventSyncReferredStake {
+            era: self.stake_manager.latest_era,
+            token_account: self.token_account.key(),
+            referrer: self.referred_stake.referrer,
+            rsol_amount: self.referred_stake.rsol_amount
+        });
+        Ok(())
+    }
+}
@@ -0,0 +1,176 @@
+use crate::{Errors, StakeManager, UnstakeAccount, UnstakeIndex};
+use anchor_lang::prelude::*;
+use anchor_spl::token::{
+    transfer as transfer_token, Mint, Token, TokenAccount, Transfer as TransferToken,
+};
+
+use mint_manager_program::cpi::accounts::MintToken;
+use mint_manager_program::program::MintManagerProgram;
+use mint_manager_program::{self, MintManager};
+
+#[derive(Accounts)]
+pub struct CancelUnstake<'info> {
+    #[account(
+        mut,
+        has_one = rsol_mint @ Errors::MintAccountNotMatch,
+        has_one = fee_recipient @ Errors::FeeRecipientNotMatch,
+        constraint = stake_manager.is_upgraded() @ Errors::StakeManagerNotUpgraded
+    )]
+    pub stake_manager: Box<Account<'info, StakeManager>>,
+
+    #[account(
+        seeds = [
+            &stake_manager.key().to_bytes(),
+            StakeManager::POOL_SEED,
+        ],
+        bump = stake_manager.pool_seed_bump
+    )]
+    pub stake_pool: SystemAccount<'info>,
+
+    #[account(
+        mut,
+        has_one = stake_manager @ Errors::InvalidUnstakeAccount,
+        has_one = recipient @ Errors::UnstakeRecipientNotMatch
+    )]
+    pub unstake_account: Box<Account<'info, UnstakeAccount>>,
+
+    #[account(
+        mut,
+        seeds = [
+            &stake_manager.key().to_bytes(),
+            &recipient.key().to_bytes(),
+            UnstakeIndex::SEED,
+        ],
+        bump
+    )]
+    pub unstake_index: Box<Account<'info, UnstakeIndex>>,
+
+    #[account(mut)]
+    pub recipient: Signer<'info>,
+
+    #[account(
+        has_one = rsol_mint @ Errors::MintAccountNotMatch
+    )]
+    pub mint_manager: Box<Account<'info, MintManager>>,
+
+    #[account(mut)]
+    pub rsol_mint: Box<Account<'info, Mint>>,
+
+    #[account(
+        mut,
+        token::mint = stake_manager.rsol_mint
+    )]
+    pub mint_to: Box<Account<'info, TokenAccount>>,
+
+    #[account(mut)]
+    pub fee_recipient: Box<Account<'info, TokenAccount>>,
+
+    /// CHECK:  check on mint manager program
+    pub mint_authority: UncheckedAccount<'info>,
+
+    pub mint_manager_program: Program<'info, MintManagerProgram>,
+    pub token_program: Program<'info, Token>,
+}
+
+#[event]
+pub struct EventCancelUnstake {
+    pub era: u64,
+    pub staker: Pubkey,
+    pub unstake_account: Pubkey,
+    pub mint_to: Pubkey,
+    pub sol_amount: u64,
+    pub rsol_amount: u64,
+    pub fee_refund: u64,
+}
+
+impl<'info> CancelUnstake<'info> {
+    pub fn process(&mut self) -> Result<()> {
+        // only tickets whose unbond is still netted in the current era can be canceled
+        require_eq!(
+            self.unstake_account.created_era,
+            self.stake_manager.latest_era,
+            Errors::UnstakeAccountNotCancelable
+        );
+
+        let sol_amount = self.unstake_account.amount;
+        require_gt!(sol_amount, 0, Errors::UnstakeAccountAmountZero);
+
+        let rsol_amount = self.stake_manager.calc_rsol_amount(sol_amount)?;
+
+        // a refunded fee comes back out of the fee recipient, which approves the pool as its delegate
+        let fee_refund = if self.stake_manager.cancel_unstake_fee_refund {
+            self.unstake_account.unstake_fee
+        } else {
+            0
+        };
+        if fee_refund > 0 {
+            require!(
+                self.fee_recipient.delegate.contains(self.stake_pool.key)
+                    && self.fee_recipient.delegated_amount >= fee_refund,
+                Errors::FeeRefundNotApproved
+            );
+
+            transfer_token(
+                CpiContext::new_with_signer(
+                    self.token_program.to_account_info(),
+                    TransferToken {
+                        from: self.fee_recipient.to_account_info(),
+                        to: self.mint_to.to_account_info(),
+                        authority: self.stake_pool.to_account_info(),
+                    },
+                    &[&[
+                        &self.stake_manager.key().to_bytes(),
+                        StakeManager::POOL_SEED,
+                        &[self.stake_manager.pool_seed_bump],
+                    ]],
+                ),
+                fee_refund,
+            )?;
+        }
+
+        // mint rsol
+        let cpi_program = self.mint_manager_program.to_account_info();
+        let cpi_accounts = MintToken {
+            mint_manager: self.mint_manager.to_account_info(),
+            rsol_mint: self.rsol_mint.to_account_info(),
+            mint_to: self.mint_to.to_account_info(),
+            mint_authority: self.mint_authority.to_account_info(),
+            ext_mint_authority: self.stake_pool.to_account_info(),
+            token_program: self.token_program.to_account_info(),
+        };
+        mint_manager_program::cpi::mint_token(
+            CpiContext::new(cpi_program, cpi_accounts).with_signer(&[&[
+                &self.stake_manager.key().to_bytes(),
+                StakeManager::POOL_SEED,
+                &[self.stake_manager.pool_seed_bump],
+            ]]),
+            rsol_amount,
+        )?;
+
+        self.stake_manager.era_unbond -= sol_amount;
+        self.stake_manager.total_unstake_unclaimed = self
+            .stake_manager
+            .total_unstake_unclaimed
+            .saturating_sub(sol_amount);
+        self.stake_manager.active += sol_amount;
+        self.stake_manager.total_rsol_supply += rsol_amount;
+
+        self.unstake_account
+            .close(self.recipient.to_account_info())?;
+
+        self.unstake_index
+            .open_tickets
+            .retain(|&e| e != self.unstake_account.key());
+
+        emit!(EventCancelUnstake {
+            era: self.stake_manager.latest_era,
+            staker: self.recipient.key(),
+            unstake_account: self.unstake_account.key(),
+            mint_to: self.mint_to.key(),
+            sol_amount,
+            rsol_amount,
+            fee_refund
+        });
+        Ok(())
+    }
+}
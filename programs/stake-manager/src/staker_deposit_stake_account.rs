@@ -0,0 +1,226 @@
+use crate::{Errors, StakeManager, ValidatorRegistry, ValidatorStatus};
+use anchor_lang::prelude::*;
+use anchor_lang::solana_program::stake::state::StakeAuthorize;
+use anchor_lang::solana_program::sysvar::stake_history::{self, StakeHistory};
+use anchor_spl::stake::{authorize, Authorize, Stake, StakeAccount};
+use anchor_spl::token::{Mint, Token, TokenAccount};
+
+use mint_manager_program::cpi::accounts::MintToken;
+use mint_manager_program::program::MintManagerProgram;
+use mint_manager_program::{self, MintManager};
+
+#[derive(Accounts)]
+pub struct DepositStakeAccount<'info> {
+    #[account(
+        mut,
+        has_one = rsol_mint @Errors::MintAccountNotMatch,
+        constraint = stake_manager.is_upgraded() @ Errors::StakeManagerNotUpgraded
+    )]
+    pub stake_manager: Box<Account<'info, StakeManager>>,
+
+    #[account(
+        mut,
+        address = stake_manager.validator_registry @ Errors::ValidatorRegistryNotMatch
+    )]
+    pub validator_registry: Box<Account<'info, ValidatorRegistry>>,
+
+    #[account(
+        seeds = [
+            &stake_manager.key().to_bytes(),
+            StakeManager::POOL_SEED,
+        ],
+        bump = stake_manager.pool_seed_bump
+    )]
+    pub stake_pool: SystemAccount<'info>,
+
+    #[account(mut)]
+    pub stake_account: Box<Account<'info, StakeAccount>>,
+
+    pub stake_authority: Signer<'info>,
+
+    #[account(
+        has_one = rsol_mint @Errors::MintAccountNotMatch
+    )]
+    pub mint_manager: Box<Account<'info, MintManager>>,
+
+    #[account(mut)]
+    pub rsol_mint: Box<Account<'info, Mint>>,
+
+    #[account(
+        mut,
+        token::mint = stake_manager.rsol_mint
+    )]
+    pub mint_to: Box<Account<'info, TokenAccount>>,
+
+    /// CHECK:  check on mint manager program
+    pub mint_authority: UncheckedAccount<'info>,
+
+    pub clock: Sysvar<'info, Clock>,
+    /// CHECK: stake history
+    #[account(address = stake_history::ID)]
+    pub stake_history: UncheckedAccount<'info>,
+    pub mint_manager_program: Program<'info, MintManagerProgram>,
+    pub stake_program: Program<'info, Stake>,
+    pub token_program: Program<'info, Token>,
+}
+
+#[event]
+pub struct EventDepositStakeAccount {
+    pub era: u64,
+    pub staker: Pubkey,
+    pub stake_account: Pubkey,
+    pub mint_to: Pubkey,
+    pub stake_amount: u64,
+    pub rsol_amount: u64,
+}
+
+impl<'info> DepositStakeAccount<'info> {
+    pub fn process(&mut self) -> Result<()> {
+        require!(
+            !self
+                .stake_manager
+                .stake_accounts
+                .contains(&self.stake_account.key()),
+            Errors::StakeAccountAlreadyExist
+        );
+
+        require_gt!(
+            self.stake_manager.stake_accounts_len_limit,
+            self.stake_manager.stake_accounts.len() as u64,
+            Errors::StakeAccountsLenOverLimit
+        );
+
+        let meta = self
+            .stake_account
+            .meta()
+            .ok_or_else(|| error!(Errors::DelegationEmpty))?;
+
+        require!(
+            !meta.lockup.is_in_force(&self.clock, None),
+            Errors::StakeAccountWithLockup
+        );
+
+        require_keys_eq!(
+            meta.authorized.staker,
+            self.stake_authority.key(),
+            Errors::AuthorityNotMatch
+        );
+
+        require_keys_eq!(
+            meta.authorized.withdrawer,
+            self.stake_authority.key(),
+            Errors::AuthorityNotMatch
+        );
+
+        let delegation = self
+            .stake_account
+            .delegation()
+            .ok_or_else(|| error!(Errors::DelegationEmpty))?;
+
+        // require stake is fully active: not deactivating and done warming up
+        require_eq!(
+            delegation.deactivation_epoch,
+            u64::MAX,
+            Errors::StakeAccountNotActive
+        );
+
+        let stake_history = StakeHistory::from_account_info(&self.stake_history)?;
+        let status = delegation.stake_activating_and_deactivating(
+            self.clock.epoch,
+            Some(&stake_history),
+            Some(0),
+        );
+        require!(
+            status.effective > 0 && status.activating == 0,
+            Errors::StakeAccountNotActive
+        );
+
+        require!(
+            self.stake_manager
+                .validators
+                .contains(&delegation.voter_pubkey),
+            Errors::ValidatorNotExist
+        );
+
+        // delinquent, draining or removed validators take no new stake
+        require!(
+            self.validator_registry
+                .get(&delegation.voter_pubkey)
+                .map(|e| e.status)
+                == Some(ValidatorStatus::Active),
+            Errors::ValidatorNotActive
+        );
+
+        // the effective stake and any lamports above it and the rent reserve are credited,
+        // the excess is moved to the pool at the next era update active
+        let excess_amount = self
+            .stake_account
+            .get_lamports()
+            .saturating_sub(status.effective)
+            .saturating_sub(meta.rent_exempt_reserve);
+        let stake_amount = status.effective + excess_amount;
+        require_gte!(
+            stake_amount,
+            self.stake_manager.min_stake_amount,
+            Errors::StakeAmountTooLow
+        );
+
+        let rsol_amount = self.stake_manager.calc_rsol_amount(stake_amount)?;
+
+        // move staker and withdrawer authority to the pool
+        for stake_authorize in [StakeAuthorize::Staker, StakeAuthorize::Withdrawer] {
+            authorize(
+                CpiContext::new(
+                    self.stake_program.to_account_info(),
+                    Authorize {
+                        stake: self.stake_account.to_account_info(),
+                        authorized: self.stake_authority.to_account_info(),
+                        new_authorized: self.stake_pool.to_account_info(),
+                        clock: self.clock.to_account_info(),
+                    },
+                ),
+                stake_authorize,
+                None,
+            )?;
+        }
+
+        // mint rsol
+        let cpi_program = self.mint_manager_program.to_account_info();
+        let cpi_accounts = MintToken {
+            mint_manager: self.mint_manager.to_account_info(),
+            rsol_mint: self.rsol_mint.to_account_info(),
+            mint_to: self.mint_to.to_account_info(),
+            mint_authority: self.mint_authority.to_account_info(),
+            ext_mint_authority: self.stake_pool.to_account_info(),
+            token_program: self.token_program.to_account_info(),
+        };
+        mint_manager_program::cpi::mint_token(
+            CpiContext::new(cpi_program, cpi_accounts).with_signer(&[&[
+                &self.stake_manager.key().to_bytes(),
+                StakeManager::POOL_SEED,
+                &[self.stake_manager.pool_seed_bump],
+            ]]),
+            rsol_amount,
+        )?;
+
+        self.stake_manager.active += stake_amount;
+        self.stake_manager.total_rsol_supply += rsol_amount;
+        self.stake_manager
+            .stake_accounts
+            .push(self.stake_account.key());
+        self.validator_registry
+            .add_active_stake(&delegation.voter_pubkey, status.effective)?;
+        self.validator_registry
+            .add_stake_account(&delegation.voter_pubkey)?;
+
+        emit!(EventDepositStakeAccount {
+            era: self.stake_manager.latest_era,
+            staker: self.stake_authority.key(),
+            stake_account: self.stake_account.key(),
+            mint_to: self.mint_to.key(),
+            stake_amount,
+            rsol_amount
+        });
+        Ok(())
+    }
+}
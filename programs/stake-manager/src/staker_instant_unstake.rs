@@ -0,0 +1,175 @@
+use crate::{Errors, ReferralAccount, ReferredStake, StakeManager};
+use anchor_lang::prelude::*;
+use anchor_lang::system_program::{transfer, Transfer};
+use anchor_spl::token::{burn, Burn, Mint, Token, TokenAccount};
+
+#[derive(Accounts)]
+pub struct InstantUnstake<'info> {
+    #[account(
+        mut,
+        has_one = rsol_mint @ Errors::MintAccountNotMatch,
+        constraint = stake_manager.is_upgraded() @ Errors::StakeManagerNotUpgraded
+    )]
+    pub stake_manager: Box<Account<'info, StakeManager>>,
+
+    #[account(
+        mut,
+        seeds = [
+            &stake_manager.key().to_bytes(),
+            StakeManager::RESERVE_SEED,
+        ],
+        bump = stake_manager.reserve_seed_bump
+    )]
+    pub reserve_pool: SystemAccount<'info>,
+
+    #[account(mut)]
+    pub rsol_mint: Box<Account<'info, Mint>>,
+
+    #[account(
+        mut,
+        token::mint = stake_manager.rsol_mint,
+    )]
+    pub burn_rsol_from: Box<Account<'info, TokenAccount>>,
+
+    pub burn_rsol_authority: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [
+            &stake_manager.key().to_bytes(),
+            &burn_rsol_from.key().to_bytes(),
+            ReferredStake::SEED,
+        ],
+        bump
+    )]
+    /// CHECK: only exists for token accounts staked with a referrer, updated in process
+    pub referred_stake: UncheckedAccount<'info>,
+
+    #[account(mut)]
+    pub referral_account: Option<Box<Account<'info, ReferralAccount>>>,
+
+    #[account(
+        mut,
+        address = burn_rsol_from.owner @ Errors::UnstakeRecipientNotMatch
+    )]
+    pub recipient: SystemAccount<'info>,
+
+    pub system_program: Program<'info, System>,
+    pub token_program: Program<'info, Token>,
+}
+
+#[event]
+pub struct EventInstantUnstake {
+    pub era: u64,
+    pub staker: Pubkey,
+    pub burn_rsol_from: Pubkey,
+    pub unstake_amount: u64,
+    pub sol_amount: u64,
+    pub instant_unstake_fee: u64,
+    pub min_sol_out: u64,
+    pub reserve_balance: u64,
+    pub reserve_utilization: u64,
+}
+
+impl<'info> InstantUnstake<'info> {
+    pub fn process(&mut self, unstake_amount: u64, min_sol_out: u64) -> Result<()> {
+        require_gt!(unstake_amount, 0, Errors::UnstakeAmountIsZero);
+        require_gt!(
+            self.stake_manager.reserve_target,
+            0,
+            Errors::ReserveNotEnabled
+        );
+
+        if self
+            .burn_rsol_from
+            .delegate
+            .contains(self.burn_rsol_authority.key)
+        {
+            require_gte!(
+                self.burn_rsol_from.delegated_amount,
+                unstake_amount,
+                Errors::BalanceNotEnough
+            );
+        } else if self.burn_rsol_authority.key() == self.burn_rsol_from.owner {
+            require_gte!(
+                self.burn_rsol_from.amount,
+                unstake_amount,
+                Errors::BalanceNotEnough
+            );
+        } else {
+            return err!(Errors::AuthorityNotMatch);
+        }
+
+        let sol_amount = self.stake_manager.calc_sol_amount(unstake_amount)?;
+        require_gte!(
+            self.stake_manager.reserve_balance,
+            sol_amount,
+            Errors::ReserveBalanceNotEnough
+        );
+
+        // the fee stays in the reserve and accrues to rsol holders
+        let instant_unstake_fee = self.stake_manager.calc_instant_unstake_fee(sol_amount)?;
+        let withdraw_amount = sol_amount - instant_unstake_fee;
+        require_gte!(withdraw_amount, min_sol_out, Errors::SlippageExceeded);
+
+        // burn rsol
+        burn(
+            CpiContext::new(
+                self.token_program.to_account_info(),
+                Burn {
+                    mint: self.rsol_mint.to_account_info(),
+                    from: self.burn_rsol_from.to_account_info(),
+                    authority: self.burn_rsol_authority.to_account_info(),
+                },
+            ),
+            unstake_amount,
+        )?;
+
+        transfer(
+            CpiContext::new_with_signer(
+                self.system_program.to_account_info(),
+                Transfer {
+                    from: self.reserve_pool.to_account_info(),
+                    to: self.recipient.to_account_info(),
+                },
+                &[&[
+                    &self.stake_manager.key().to_bytes(),
+                    StakeManager::RESERVE_SEED,
+                    &[self.stake_manager.reserve_seed_bump],
+                ]],
+            ),
+            withdraw_amount,
+        )?;
+
+        self.stake_manager.total_rsol_supply -= unstake_amount;
+
+        // burned rsol is no longer referred
+        self.burn_rsol_from.reload()?;
+        ReferredStake::sync(
+            &self.referred_stake,
+            self.referral_account.as_deref_mut().map(|e| &mut **e),
+            &mut self.stake_manager,
+            self.burn_rsol_from.amount,
+        )?;
+        self.stake_manager.active -= withdraw_amount;
+        self.stake_manager.reserve_balance -= withdraw_amount;
+        self.stake_manager.reserve_fee_collected += instant_unstake_fee;
+
+        let reserve_utilization = self
+            .stake_manager
+            .calc_reserve_utilization(self.stake_manager.reserve_balance)?;
+
+        emit!(EventInstantUnstake {
+            era: self.stake_manager.latest_era,
+            staker: self.burn_rsol_from.owner,
+            burn_rsol_from: self.burn_rsol_from.key(),
+            unstake_amount,
+            sol_amount: withdraw_amount,
+            instant_unstake_fee,
+            min_sol_out,
+            reserve_balance: self.stake_manager.reserve_balance,
+            reserve_utilization
+        });
+        Ok(())
+    }
+}
@@ -13,6 +13,7 @@ pub struct Stake<'info> {
     #[account(
         mut,
         has_one = rsol_mint @Errors::MintAccountNotMatch,
+        constraint = stake_manager.is_upgraded() @ Errors::StakeManagerNotUpgraded
     )]
     pub stake_manager: Box<Account<'info, StakeManager>>,
 
@@ -62,10 +63,11 @@ pub struct EventStake {
     pub mint_to: Pubkey,
     pub stake_amount: u64,
     pub rsol_amount: u64,
+    pub min_rsol_out: u64,
 }
 
 impl<'info> Stake<'info> {
-    pub fn process(&mut self, stake_amount: u64) -> Result<()> {
+    pub fn process(&mut self, stake_amount: u64, min_rsol_out: u64) -> Result<u64> {
         require_gte!(
             stake_amount,
             self.stake_manager.min_stake_amount,
@@ -76,6 +78,7 @@ impl<'info> Stake<'info> {
         require_gte!(user_balance, stake_amount, Errors::BalanceNotEnough);
 
         let rsol_amount = self.stake_manager.calc_rsol_amount(stake_amount)?;
+        require_gte!(rsol_amount, min_rsol_out, Errors::SlippageExceeded);
 
         self.stake_manager.era_bond += stake_amount;
         self.stake_manager.active += stake_amount;
@@ -118,8 +121,9 @@ impl<'info> Stake<'info> {
             staker: self.from.key(),
             mint_to: self.mint_to.key(),
             stake_amount,
-            rsol_amount
+            rsol_amount,
+            min_rsol_out
         });
-        Ok(())
+        Ok(rsol_amount)
     }
 }
@@ -0,0 +1,140 @@
+use crate::{Errors, StakeManager, UnstakeAccount, UnstakeIndex, MAX_OPEN_UNSTAKE_TICKETS};
+use anchor_lang::{prelude::*, system_program};
+
+#[derive(Accounts)]
+pub struct TransferUnstakeTicket<'info> {
+    #[account(constraint = stake_manager.is_upgraded() @ Errors::StakeManagerNotUpgraded)]
+    pub stake_manager: Box<Account<'info, StakeManager>>,
+
+    #[account(
+        mut,
+        has_one = stake_manager @ Errors::InvalidUnstakeAccount,
+        has_one = recipient @ Errors::UnstakeRecipientNotMatch
+    )]
+    pub unstake_account: Box<Account<'info, UnstakeAccount>>,
+
+    pub recipient: Signer<'info>,
+}
+
+#[event]
+pub struct EventTransferUnstakeTicket {
+    pub era: u64,
+    pub unstake_account: Pubkey,
+    pub from_recipient: Pubkey,
+    pub to_recipient: Pubkey,
+    pub amount: u64,
+}
+
+impl<'info> TransferUnstakeTicket<'info> {
+    // only offers the ticket, it moves once the new recipient accepts it,
+    // a default new recipient withdraws the offer
+    pub fn process(&mut self, new_recipient: Pubkey) -> Result<()> {
+        require_keys_neq!(
+            new_recipient,
+            self.recipient.key(),
+            Errors::UnstakeRecipientNotMatch
+        );
+
+        self.unstake_account.pending_recipient = new_recipient;
+
+        emit!(EventTransferUnstakeTicket {
+            era: self.stake_manager.latest_era,
+            unstake_account: self.unstake_account.key(),
+            from_recipient: self.recipient.key(),
+            to_recipient: new_recipient,
+            amount: self.unstake_account.amount
+        });
+        Ok(())
+    }
+}
+
+#[derive(Accounts)]
+pub struct AcceptUnstakeTicket<'info> {
+    #[account(constraint = stake_manager.is_upgraded() @ Errors::StakeManagerNotUpgraded)]
+    pub stake_manager: Box<Account<'info, StakeManager>>,
+
+    #[account(
+        mut,
+        has_one = stake_manager @ Errors::InvalidUnstakeAccount,
+        constraint = unstake_account.pending_recipient == new_recipient.key() @ Errors::UnstakeRecipientNotMatch
+    )]
+    pub unstake_account: Box<Account<'info, UnstakeAccount>>,
+
+    #[account(
+        mut,
+        seeds = [
+            &stake_manager.key().to_bytes(),
+            &unstake_account.recipient.to_bytes(),
+            UnstakeIndex::SEED,
+        ],
+        bump
+    )]
+    /// CHECK: may not exist for legacy tickets, updated in process
+    pub from_unstake_index: UncheckedAccount<'info>,
+
+    #[account(
+        init_if_needed,
+        payer = new_recipient,
+        space = 8 + UnstakeIndex::INIT_SPACE,
+        seeds = [
+            &stake_manager.key().to_bytes(),
+            &new_recipient.key().to_bytes(),
+            UnstakeIndex::SEED,
+        ],
+        bump,
+    )]
+    pub to_unstake_index: Box<Account<'info, UnstakeIndex>>,
+
+    // withdraw pays out to a system account, so only one can take the ticket
+    #[account(
+        mut,
+        owner = system_program::ID
+    )]
+    pub new_recipient: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[event]
+pub struct EventAcceptUnstakeTicket {
+    pub era: u64,
+    pub unstake_account: Pubkey,
+    pub from_recipient: Pubkey,
+    pub to_recipient: Pubkey,
+    pub amount: u64,
+}
+
+impl<'info> AcceptUnstakeTicket<'info> {
+    pub fn process(&mut self) -> Result<()> {
+        require_gt!(
+            MAX_OPEN_UNSTAKE_TICKETS,
+            self.to_unstake_index.open_tickets.len(),
+            Errors::UnstakeTicketsOverLimit
+        );
+
+        // first ticket of the new owner initializes the index
+        if self.to_unstake_index.owner == Pubkey::default() {
+            self.to_unstake_index.stake_manager = self.stake_manager.key();
+            self.to_unstake_index.owner = self.new_recipient.key();
+        }
+
+        // the ticket address stays derived from its creator and id
+        UnstakeIndex::remove_open_ticket(&self.from_unstake_index, &self.unstake_account.key())?;
+        self.to_unstake_index
+            .open_tickets
+            .push(self.unstake_account.key());
+
+        let from_recipient = self.unstake_account.recipient;
+        self.unstake_account.recipient = self.new_recipient.key();
+        self.unstake_account.pending_recipient = Pubkey::default();
+
+        emit!(EventAcceptUnstakeTicket {
+            era: self.stake_manager.latest_era,
+            unstake_account: self.unstake_account.key(),
+            from_recipient,
+            to_recipient: self.new_recipient.key(),
+            amount: self.unstake_account.amount
+        });
+        Ok(())
+    }
+}
@@ -1,5 +1,8 @@
-use crate::{Errors, StakeManager, UnstakeAccount};
-use anchor_lang::prelude::*;
+use crate::{
+    Errors, ReferralAccount, ReferredStake, StakeManager, UnstakeAccount, UnstakeIndex,
+    MAX_OPEN_UNSTAKE_TICKETS,
+};
+use anchor_lang::{prelude::*, system_program};
 use anchor_spl::token::{
     burn, transfer as transfer_token, Burn, Mint, Token, TokenAccount, Transfer as TransferToken,
 };
@@ -9,7 +12,8 @@ pub struct Unstake<'info> {
     #[account(
         mut, 
         has_one = fee_recipient @ Errors::FeeRecipientNotMatch,
-        has_one = rsol_mint @ Errors::MintAccountNotMatch
+        has_one = rsol_mint @ Errors::MintAccountNotMatch,
+        constraint = stake_manager.is_upgraded() @ Errors::StakeManagerNotUpgraded
     )]
     pub stake_manager: Box<Account<'info, StakeManager>>,
 
@@ -25,16 +29,58 @@ pub struct Unstake<'info> {
     pub burn_rsol_authority: Signer<'info>,
 
     #[account(
-        zero,
-        rent_exempt = enforce
+        mut,
+        seeds = [
+            &stake_manager.key().to_bytes(),
+            &burn_rsol_from.key().to_bytes(),
+            ReferredStake::SEED,
+        ],
+        bump
+    )]
+    /// CHECK: only exists for token accounts staked with a referrer, updated in process
+    pub referred_stake: UncheckedAccount<'info>,
+
+    #[account(mut)]
+    pub referral_account: Option<Box<Account<'info, ReferralAccount>>>,
+
+    #[account(
+        init_if_needed,
+        payer = rent_payer,
+        space = 8 + UnstakeIndex::INIT_SPACE,
+        seeds = [
+            &stake_manager.key().to_bytes(),
+            &burn_rsol_from.owner.to_bytes(),
+            UnstakeIndex::SEED,
+        ],
+        bump,
+    )]
+    pub unstake_index: Box<Account<'info, UnstakeIndex>>,
+
+    #[account(
+        init,
+        payer = rent_payer,
+        space = 8 + UnstakeAccount::INIT_SPACE,
+        seeds = [
+            &stake_manager.key().to_bytes(),
+            &burn_rsol_from.owner.to_bytes(),
+            UnstakeAccount::SEED,
+            &unstake_index.next_ticket_id.to_le_bytes(),
+        ],
+        bump,
     )]
     pub unstake_account: Box<Account<'info, UnstakeAccount>>,
 
     #[account(mut)]
     pub fee_recipient: Box<Account<'info, TokenAccount>>,
 
+    #[account(
+        mut,
+        owner = system_program::ID
+    )]
+    pub rent_payer: Signer<'info>,
+
     pub clock: Sysvar<'info, Clock>,
-    pub rent: Sysvar<'info, Rent>,
+    pub system_program: Program<'info, System>,
     pub token_program: Program<'info, Token>,
 }
 
@@ -44,15 +90,23 @@ pub struct EventUnstake {
     pub staker: Pubkey,
     pub burn_rsol_from: Pubkey,
     pub unstake_account: Pubkey,
+    pub ticket_id: u64,
     pub unstake_amount: u64,
     pub sol_amount: u64,
     pub unstake_fee: u64,
+    pub min_sol_out: u64,
 }
 
 impl<'info> Unstake<'info> {
-    pub fn process(&mut self, unstake_amount: u64) -> Result<()> {
+    pub fn process(&mut self, unstake_amount: u64, min_sol_out: u64) -> Result<()> {
         require_gt!(unstake_amount, 0, Errors::UnstakeAmountIsZero);
 
+        require_gt!(
+            MAX_OPEN_UNSTAKE_TICKETS,
+            self.unstake_index.open_tickets.len(),
+            Errors::UnstakeTicketsOverLimit
+        );
+
         if self
             .burn_rsol_from
             .delegate
@@ -93,7 +147,10 @@ impl<'info> Unstake<'info> {
         }
 
         let sol_amount = self.stake_manager.calc_sol_amount(unbond_amount)?;
+        require_gte!(sol_amount, min_sol_out, Errors::SlippageExceeded);
+
         self.stake_manager.era_unbond += sol_amount;
+        self.stake_manager.total_unstake_unclaimed += sol_amount;
         self.stake_manager.active -= sol_amount;
 
         // burn rsol
@@ -111,21 +168,47 @@ impl<'info> Unstake<'info> {
 
         self.stake_manager.total_rsol_supply -= unbond_amount;
 
+        // burned rsol is no longer referred
+        self.burn_rsol_from.reload()?;
+        ReferredStake::sync(
+            &self.referred_stake,
+            self.referral_account.as_deref_mut().map(|e| &mut **e),
+            &mut self.stake_manager,
+            self.burn_rsol_from.amount,
+        )?;
+
         self.unstake_account.set_inner(UnstakeAccount {
             stake_manager: self.stake_manager.key(),
             recipient: self.burn_rsol_from.owner,
             amount: sol_amount,
             created_epoch: self.clock.epoch,
+            created_era: self.stake_manager.latest_era,
+            unstake_fee,
+            pending_recipient: Pubkey::default(),
         });
 
+        // first ticket of this owner initializes the index
+        if self.unstake_index.owner == Pubkey::default() {
+            self.unstake_index.stake_manager = self.stake_manager.key();
+            self.unstake_index.owner = self.burn_rsol_from.owner;
+        }
+
+        let ticket_id = self.unstake_index.next_ticket_id;
+        self.unstake_index.next_ticket_id += 1;
+        self.unstake_index
+            .open_tickets
+            .push(self.unstake_account.key());
+
         emit!(EventUnstake{ 
             era: self.stake_manager.latest_era, 
             staker: self.burn_rsol_from.owner, 
             burn_rsol_from: self.burn_rsol_from.key(), 
             unstake_account: self.unstake_account.key(),
+            ticket_id,
             unstake_amount, 
             sol_amount, 
-            unstake_fee 
+            unstake_fee,
+            min_sol_out
         });
         
         Ok(())
@@ -1,10 +1,13 @@
-use crate::{Errors, StakeManager, UnstakeAccount};
+use crate::{Errors, StakeManager, UnstakeAccount, UnstakeIndex};
 use anchor_lang::prelude::*;
 use anchor_lang::system_program::{transfer, Transfer};
 
 #[derive(Accounts)]
 pub struct Withdraw<'info> {
-    #[account(mut)]
+    #[account(
+        mut,
+        constraint = stake_manager.is_upgraded() @ Errors::StakeManagerNotUpgraded
+    )]
     pub stake_manager: Box<Account<'info, StakeManager>>,
 
     #[account(
@@ -17,11 +20,20 @@ pub struct Withdraw<'info> {
     )]
     pub stake_pool: SystemAccount<'info>,
 
+    #[account(mut)]
+    pub unstake_account: Account<'info, UnstakeAccount>,
+
     #[account(
         mut,
-        close = recipient,
+        seeds = [
+            &stake_manager.key().to_bytes(),
+            &unstake_account.recipient.to_bytes(),
+            UnstakeIndex::SEED,
+        ],
+        bump
     )]
-    pub unstake_account: Account<'info, UnstakeAccount>,
+    /// CHECK: may not exist for legacy tickets, updated in process
+    pub unstake_index: UncheckedAccount<'info>,
 
     #[account(
         mut,
@@ -41,8 +53,17 @@ pub struct EventWithdraw {
     pub withdraw_amount: u64,
 }
 
+#[event]
+pub struct EventWithdrawPartial {
+    pub era: u64,
+    pub staker: Pubkey,
+    pub unstake_account: Pubkey,
+    pub withdraw_amount: u64,
+    pub remaining_amount: u64,
+}
+
 impl<'info> Withdraw<'info> {
-    pub fn process(&mut self) -> Result<()> {
+    pub fn process(&mut self, allow_partial: bool) -> Result<()> {
         require_keys_eq!(
             self.unstake_account.stake_manager,
             self.stake_manager.key(),
@@ -61,13 +82,20 @@ impl<'info> Withdraw<'info> {
             Errors::UnstakeAccountNotClaimable
         );
 
-        let pool_balance = self.stake_pool.lamports();
-        let withdraw_amount = self.unstake_account.amount;
+        let unstake_amount = self.unstake_account.amount;
 
-        let available_for_withdraw = pool_balance - self.stake_manager.rent_exempt_for_pool_acc;
-        if withdraw_amount > available_for_withdraw {
-            return err!(Errors::PoolBalanceNotEnough);
-        }
+        let available_for_withdraw = self
+            .stake_manager
+            .calc_available_for_withdraw(self.stake_pool.lamports());
+        let withdraw_amount = if unstake_amount > available_for_withdraw {
+            if !allow_partial {
+                return err!(Errors::PoolBalanceNotEnough);
+            }
+            available_for_withdraw
+        } else {
+            unstake_amount
+        };
+        require_gt!(withdraw_amount, 0, Errors::PoolBalanceNotEnough);
 
         transfer(
             CpiContext::new_with_signer(
@@ -85,6 +113,31 @@ impl<'info> Withdraw<'info> {
             withdraw_amount,
         )?;
 
+        self.stake_manager.total_unstake_unclaimed = self
+            .stake_manager
+            .total_unstake_unclaimed
+            .saturating_sub(withdraw_amount);
+
+        let remaining_amount = unstake_amount - withdraw_amount;
+        if remaining_amount > 0 {
+            // keep the ticket open for the amount still owed
+            self.unstake_account.amount = remaining_amount;
+
+            emit!(EventWithdrawPartial {
+                era: self.stake_manager.latest_era,
+                staker: self.recipient.key(),
+                unstake_account: self.unstake_account.key(),
+                withdraw_amount,
+                remaining_amount
+            });
+            return Ok(());
+        }
+
+        self.unstake_account
+            .close(self.recipient.to_account_info())?;
+
+        UnstakeIndex::remove_open_ticket(&self.unstake_index, &self.unstake_account.key())?;
+
         emit!(EventWithdraw {
             era: self.stake_manager.latest_era,
             staker: self.recipient.key(),
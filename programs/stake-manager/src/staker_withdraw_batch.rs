@@ -0,0 +1,144 @@
+use crate::{Errors, EventWithdraw, StakeManager, UnstakeAccount, UnstakeIndex};
+use anchor_lang::prelude::*;
+use anchor_lang::system_program::{transfer, Transfer};
+
+#[derive(Accounts)]
+pub struct WithdrawBatch<'info> {
+    #[account(
+        mut,
+        constraint = stake_manager.is_upgraded() @ Errors::StakeManagerNotUpgraded
+    )]
+    pub stake_manager: Box<Account<'info, StakeManager>>,
+
+    #[account(
+        mut,
+        seeds = [
+            &stake_manager.key().to_bytes(),
+            StakeManager::POOL_SEED,
+        ],
+        bump = stake_manager.pool_seed_bump
+    )]
+    pub stake_pool: SystemAccount<'info>,
+
+    #[account(
+        mut,
+        seeds = [
+            &stake_manager.key().to_bytes(),
+            &recipient.key().to_bytes(),
+            UnstakeIndex::SEED,
+        ],
+        bump
+    )]
+    /// CHECK: may not exist for legacy tickets, updated in process
+    pub unstake_index: UncheckedAccount<'info>,
+
+    #[account(mut)]
+    pub recipient: SystemAccount<'info>,
+
+    pub clock: Sysvar<'info, Clock>,
+    pub system_program: Program<'info, System>,
+}
+
+#[event]
+pub struct EventWithdrawBatch {
+    pub era: u64,
+    pub staker: Pubkey,
+    pub unstake_accounts_len: u64,
+    pub withdraw_amount: u64,
+}
+
+impl<'info> WithdrawBatch<'info> {
+    // unstake accounts are passed as writable remaining accounts
+    pub fn process(&mut self, unstake_account_infos: &'info [AccountInfo<'info>]) -> Result<()> {
+        require!(
+            !unstake_account_infos.is_empty(),
+            Errors::RemainingAccountsNotMatch
+        );
+
+        let mut unstake_accounts: Vec<Account<'info, UnstakeAccount>> =
+            Vec::with_capacity(unstake_account_infos.len());
+        let mut withdraw_amount: u64 = 0;
+        for unstake_account_info in unstake_account_infos {
+            require!(
+                !unstake_accounts
+                    .iter()
+                    .any(|e| e.key() == unstake_account_info.key()),
+                Errors::RemainingAccountsNotMatch
+            );
+
+            let unstake_account = Account::<UnstakeAccount>::try_from(unstake_account_info)?;
+
+            require_keys_eq!(
+                unstake_account.stake_manager,
+                self.stake_manager.key(),
+                Errors::InvalidUnstakeAccount
+            );
+
+            require_keys_eq!(
+                unstake_account.recipient,
+                self.recipient.key(),
+                Errors::UnstakeRecipientNotMatch
+            );
+
+            require_gt!(unstake_account.amount, 0, Errors::UnstakeAccountAmountZero);
+
+            require_gte!(
+                self.clock.epoch,
+                unstake_account.created_epoch + self.stake_manager.unbonding_duration,
+                Errors::UnstakeAccountNotClaimable
+            );
+
+            withdraw_amount += unstake_account.amount;
+            unstake_accounts.push(unstake_account);
+        }
+
+        let available_for_withdraw = self
+            .stake_manager
+            .calc_available_for_withdraw(self.stake_pool.lamports());
+        if withdraw_amount > available_for_withdraw {
+            return err!(Errors::PoolBalanceNotEnough);
+        }
+
+        transfer(
+            CpiContext::new_with_signer(
+                self.system_program.to_account_info(),
+                Transfer {
+                    from: self.stake_pool.to_account_info(),
+                    to: self.recipient.to_account_info(),
+                },
+                &[&[
+                    &self.stake_manager.key().to_bytes(),
+                    StakeManager::POOL_SEED,
+                    &[self.stake_manager.pool_seed_bump],
+                ]],
+            ),
+            withdraw_amount,
+        )?;
+
+        self.stake_manager.total_unstake_unclaimed = self
+            .stake_manager
+            .total_unstake_unclaimed
+            .saturating_sub(withdraw_amount);
+
+        for unstake_account in &unstake_accounts {
+            unstake_account.close(self.recipient.to_account_info())?;
+
+            UnstakeIndex::remove_open_ticket(&self.unstake_index, &unstake_account.key())?;
+
+            emit!(EventWithdraw {
+                era: self.stake_manager.latest_era,
+                staker: self.recipient.key(),
+                unstake_account: unstake_account.key(),
+                withdraw_amount: unstake_account.amount
+            });
+        }
+
+        emit!(EventWithdrawBatch {
+            era: self.stake_manager.latest_era,
+            staker: self.recipient.key(),
+            unstake_accounts_len: unstake_accounts.len() as u64,
+            withdraw_amount
+        });
+        Ok(())
+    }
+}
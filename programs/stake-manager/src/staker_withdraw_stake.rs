@@ -0,0 +1,267 @@
+use crate::{EraPhase, Errors, ReferralAccount, ReferredStake, StakeManager, ValidatorRegistry};
+use anchor_lang::prelude::*;
+use anchor_lang::{
+    solana_program::{
+        program::invoke_signed,
+        stake::{
+            self,
+            state::{StakeAuthorize, StakeStateV2},
+        },
+    },
+    system_program,
+};
+use anchor_spl::stake::{Stake, StakeAccount};
+use anchor_spl::token::{
+    burn, transfer as transfer_token, Burn, Mint, Token, TokenAccount, Transfer as TransferToken,
+};
+
+#[derive(Accounts)]
+pub struct WithdrawStake<'info> {
+    #[account(
+        mut,
+        has_one = fee_recipient @ Errors::FeeRecipientNotMatch,
+        has_one = rsol_mint @ Errors::MintAccountNotMatch,
+        constraint = stake_manager.is_upgraded() @ Errors::StakeManagerNotUpgraded
+    )]
+    pub stake_manager: Box<Account<'info, StakeManager>>,
+
+    #[account(
+        mut,
+        address = stake_manager.validator_registry @ Errors::ValidatorRegistryNotMatch
+    )]
+    pub validator_registry: Box<Account<'info, ValidatorRegistry>>,
+
+    #[account(
+        seeds = [
+            &stake_manager.key().to_bytes(),
+            StakeManager::POOL_SEED
+        ],
+        bump = stake_manager.pool_seed_bump
+    )]
+    pub stake_pool: SystemAccount<'info>,
+
+    #[account(mut)]
+    pub rsol_mint: Box<Account<'info, Mint>>,
+
+    #[account(
+        mut,
+        token::mint = stake_manager.rsol_mint,
+    )]
+    pub burn_rsol_from: Box<Account<'info, TokenAccount>>,
+
+    pub burn_rsol_authority: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [
+            &stake_manager.key().to_bytes(),
+            &burn_rsol_from.key().to_bytes(),
+            ReferredStake::SEED,
+        ],
+        bump
+    )]
+    /// CHECK: only exists for token accounts staked with a referrer, updated in process
+    pub referred_stake: UncheckedAccount<'info>,
+
+    #[account(mut)]
+    pub referral_account: Option<Box<Account<'info, ReferralAccount>>>,
+
+    #[account(mut)]
+    pub from_stake_account: Box<Account<'info, StakeAccount>>,
+
+    #[account(
+        init,
+        payer = rent_payer,
+        space = std::mem::size_of::<StakeStateV2>(),
+        owner = stake::program::ID,
+    )]
+    pub split_stake_account: Account<'info, StakeAccount>,
+
+    #[account(mut)]
+    pub fee_recipient: Box<Account<'info, TokenAccount>>,
+
+    #[account(
+        mut,
+        owner = system_program::ID
+    )]
+    pub rent_payer: Signer<'info>,
+
+    pub clock: Sysvar<'info, Clock>,
+    pub stake_program: Program<'info, Stake>,
+    pub system_program: Program<'info, System>,
+    pub token_program: Program<'info, Token>,
+}
+
+#[event]
+pub struct EventWithdrawStake {
+    pub era: u64,
+    pub staker: Pubkey,
+    pub burn_rsol_from: Pubkey,
+    pub from_stake_account: Pubkey,
+    pub split_stake_account: Pubkey,
+    pub unstake_amount: u64,
+    pub sol_amount: u64,
+    pub unstake_fee: u64,
+}
+
+impl<'info> WithdrawStake<'info> {
+    pub fn process(&mut self, unstake_amount: u64) -> Result<()> {
+        require_gt!(unstake_amount, 0, Errors::UnstakeAmountIsZero);
+
+        require!(
+            self.stake_manager.era_phase == EraPhase::Idle,
+            Errors::EraIsProcessing
+        );
+
+        require!(
+            self.stake_manager
+                .stake_accounts
+                .contains(&self.from_stake_account.key()),
+            Errors::StakeAccountNotExist
+        );
+
+        if self
+            .burn_rsol_from
+            .delegate
+            .contains(self.burn_rsol_authority.key)
+        {
+            require_gte!(
+                self.burn_rsol_from.delegated_amount,
+                unstake_amount,
+                Errors::BalanceNotEnough
+            );
+        } else if self.burn_rsol_authority.key() == self.burn_rsol_from.owner {
+            require_gte!(
+                self.burn_rsol_from.amount,
+                unstake_amount,
+                Errors::BalanceNotEnough
+            );
+        } else {
+            return err!(Errors::AuthorityNotMatch);
+        }
+
+        let delegation = self
+            .from_stake_account
+            .delegation()
+            .ok_or_else(|| error!(Errors::DelegationEmpty))?;
+
+        // require stake is active (deactivation_epoch == u64::MAX)
+        require_eq!(
+            delegation.deactivation_epoch,
+            u64::MAX,
+            Errors::StakeAccountNotActive
+        );
+
+        let unstake_fee = self.stake_manager.calc_unstake_fee(unstake_amount)?;
+        let unbond_amount = unstake_amount - unstake_fee;
+        let sol_amount = self.stake_manager.calc_sol_amount(unbond_amount)?;
+
+        // the source account must keep a delegation after the split
+        require_gt!(delegation.stake, sol_amount, Errors::AmountUnmatch);
+
+        // transfer fee
+        if unstake_fee > 0 {
+            transfer_token(
+                CpiContext::new(
+                    self.token_program.to_account_info(),
+                    TransferToken {
+                        from: self.burn_rsol_from.to_account_info(),
+                        to: self.fee_recipient.to_account_info(),
+                        authority: self.burn_rsol_authority.to_account_info(),
+                    },
+                ),
+                unstake_fee,
+            )?;
+            self.stake_manager.total_protocol_fee += unstake_fee;
+        }
+
+        // burn rsol
+        burn(
+            CpiContext::new(
+                self.token_program.to_account_info(),
+                Burn {
+                    mint: self.rsol_mint.to_account_info(),
+                    from: self.burn_rsol_from.to_account_info(),
+                    authority: self.burn_rsol_authority.to_account_info(),
+                },
+            ),
+            unbond_amount,
+        )?;
+
+        // split
+        let split_instruction = stake::instruction::split(
+            self.from_stake_account.to_account_info().key,
+            self.stake_pool.key,
+            sol_amount,
+            &self.split_stake_account.key(),
+        )
+        .last()
+        .unwrap()
+        .clone();
+
+        invoke_signed(
+            &split_instruction,
+            &[
+                self.stake_program.to_account_info(),
+                self.from_stake_account.to_account_info(),
+                self.split_stake_account.to_account_info(),
+                self.stake_pool.to_account_info(),
+            ],
+            &[&[
+                &self.stake_manager.key().to_bytes(),
+                StakeManager::POOL_SEED,
+                &[self.stake_manager.pool_seed_bump],
+            ]],
+        )?;
+
+        // hand staker and withdrawer authority to the user
+        for stake_authorize in [StakeAuthorize::Staker, StakeAuthorize::Withdrawer] {
+            invoke_signed(
+                &stake::instruction::authorize(
+                    &self.split_stake_account.key(),
+                    self.stake_pool.key,
+                    &self.burn_rsol_from.owner,
+                    stake_authorize,
+                    None,
+                ),
+                &[
+                    self.stake_program.to_account_info(),
+                    self.split_stake_account.to_account_info(),
+                    self.clock.to_account_info(),
+                    self.stake_pool.to_account_info(),
+                ],
+                &[&[
+                    &self.stake_manager.key().to_bytes(),
+                    StakeManager::POOL_SEED,
+                    &[self.stake_manager.pool_seed_bump],
+                ]],
+            )?;
+        }
+
+        self.stake_manager.active -= sol_amount;
+        self.stake_manager.total_rsol_supply -= unbond_amount;
+        self.validator_registry
+            .remove_active_stake(&delegation.voter_pubkey, sol_amount)?;
+
+        // burned rsol is no longer referred
+        self.burn_rsol_from.reload()?;
+        ReferredStake::sync(
+            &self.referred_stake,
+            self.referral_account.as_deref_mut().map(|e| &mut **e),
+            &mut self.stake_manager,
+            self.burn_rsol_from.amount,
+        )?;
+
+        emit!(EventWithdrawStake {
+            era: self.stake_manager.latest_era,
+            staker: self.burn_rsol_from.owner,
+            burn_rsol_from: self.burn_rsol_from.key(),
+            from_stake_account: self.from_stake_account.key(),
+            split_stake_account: self.split_stake_account.key(),
+            unstake_amount,
+            sol_amount,
+            unstake_fee
+        });
+        Ok(())
+    }
+}
@@ -2,7 +2,7 @@ use anchor_lang::prelude::*;
 
 pub use crate::errors::Errors;
 #[account]
-#[derive(Debug)]
+#[derive(Debug, Default)]
 pub struct StakeManager {
     pub admin: Pubkey,
     pub balancer: Pubkey,
@@ -30,6 +30,67 @@ pub struct StakeManager {
     pub stake_accounts: Vec<Pubkey>,
     pub split_accounts: Vec<Pubkey>,
     pub era_process_data: EraProcessData,
+
+    pub reserve_seed_bump: u8,
+    pub reserve_target: u64,
+    pub reserve_balance: u64,
+    pub reserve_fee_collected: u64,
+    pub era_reserve_fill: u64,
+    pub era_reserve_release: u64,
+    pub instant_unstake_min_fee_commission: u64, // decimals 9
+    pub instant_unstake_max_fee_commission: u64, // decimals 9
+
+    pub cancel_unstake_fee_refund: bool,
+
+    pub referral_fee_share: u64, // decimals 9
+    pub total_referred_rsol: u64,
+    pub referral_reward_per_rsol: u128, // decimals 18
+
+    pub crank_tip: u64,
+    pub crank_tip_era_budget: u64,
+    pub crank_tip_era_paid: u64,
+    pub crank_tip_unsettled: u64,
+    pub crank_tip_fund: u64,
+    pub total_crank_tip_paid: u64,
+
+    pub era_phase: EraPhase,
+
+    pub total_unstake_unclaimed: u64,
+
+    pub last_rate_override_era: u64,
+
+    pub era_history: Pubkey,
+
+    pub validator_registry: Pubkey,
+    pub max_validator_commission: u8, // percent
+
+    pub delinquent_stake: u64,
+
+    pub min_rebalance_amount: u64,
+
+    pub version: u8,
+}
+
+#[derive(
+    Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, AnchorSerialize, AnchorDeserialize,
+)]
+pub enum EraPhase {
+    #[default]
+    Idle,
+    Bond,
+    Unbond,
+    UpdateActive,
+    UpdateRate,
+}
+
+impl EraPhase {
+    // phases only move forward within an era, and an era ends after its rate update
+    pub fn can_transition_to(&self, next: EraPhase) -> bool {
+        match (*self, next) {
+            (EraPhase::Idle, _) | (EraPhase::UpdateRate, EraPhase::Idle) => true,
+            (current, next) => next >= current,
+        }
+    }
 }
 
 #[derive(Clone, Debug, Default, AnchorSerialize, AnchorDeserialize)]
@@ -39,41 +100,88 @@ pub struct EraProcessData {
     pub old_active: u64,
     pub new_active: u64,
     pub pending_stake_accounts: Vec<Pubkey>,
+    pub bonded_validators: Vec<Pubkey>,
+    // weighted validators failing the vote account checks this era, their share goes to the others
+    pub skipped_validators: Vec<Pubkey>,
+    pub bridged_epochs: u64,
 }
 
 impl EraProcessData {
-    pub fn is_empty(&self) -> bool {
-        return self.need_bond == 0
-            && self.need_unbond == 0
-            && self.old_active == 0
-            && self.new_active == 0
-            && self.pending_stake_accounts.is_empty();
+    pub fn is_bond_settled(&self, validator: &Pubkey) -> bool {
+        self.bonded_validators.contains(validator) || self.skipped_validators.contains(validator)
     }
 
-    pub fn need_bond(&self) -> bool {
-        return self.need_bond > 0;
+    pub fn clear_bond_progress(&mut self) {
+        self.bonded_validators.clear();
+        self.skipped_validators.clear();
     }
 
-    pub fn need_unbond(&self) -> bool {
-        return self.need_unbond > 0;
+    // the phase implied by the work left in this era
+    pub fn next_phase(&self) -> EraPhase {
+        if self.need_bond > 0 {
+            EraPhase::Bond
+        } else if self.need_unbond > 0 {
+            EraPhase::Unbond
+        } else if !self.pending_stake_accounts.is_empty() {
+            EraPhase::UpdateActive
+        } else if self.old_active != 0 || self.new_active != 0 {
+            EraPhase::UpdateRate
+        } else {
+            EraPhase::Idle
+        }
     }
 
-    pub fn need_update_active(&self) -> bool {
-        return self.need_bond == 0
-            || self.need_unbond == 0 && !self.pending_stake_accounts.is_empty();
+    // rate change limit applies per epoch, a catch-up era spans several epochs
+    pub fn rate_change_limit(&self, limit_per_epoch: u64) -> u64 {
+        limit_per_epoch.saturating_mul(self.bridged_epochs.max(1))
     }
+}
 
-    pub fn need_update_rate(&self) -> bool {
-        return self.need_bond == 0
-            && self.need_unbond == 0
-            && self.pending_stake_accounts.is_empty()
-            && self.old_active != 0
-            && self.new_active != 0;
-    }
+// stake manager layout before the upgrade, only read by upgrade_stake_manager
+#[derive(Clone, Debug, AnchorDeserialize)]
+pub struct LegacyStakeManager {
+    pub admin: Pubkey,
+    pub balancer: Pubkey,
+    pub rsol_mint: Pubkey,
+    pub fee_recipient: Pubkey,
+    pub pool_seed_bump: u8,
+    pub rent_exempt_for_pool_acc: u64,
+
+    pub min_stake_amount: u64,
+    pub unstake_fee_commission: u64,
+    pub protocol_fee_commission: u64,
+    pub rate_change_limit: u64,
+    pub stake_accounts_len_limit: u64,
+    pub split_accounts_len_limit: u64,
+    pub unbonding_duration: u64,
+
+    pub latest_era: u64,
+    pub rate: u64,
+    pub era_bond: u64,
+    pub era_unbond: u64,
+    pub active: u64,
+    pub total_rsol_supply: u64,
+    pub total_protocol_fee: u64,
+    pub validators: Vec<Pubkey>,
+    pub stake_accounts: Vec<Pubkey>,
+    pub split_accounts: Vec<Pubkey>,
+    pub era_process_data: LegacyEraProcessData,
+}
+
+#[derive(Clone, Debug, AnchorDeserialize)]
+pub struct LegacyEraProcessData {
+    pub need_bond: u64,
+    pub need_unbond: u64,
+    pub old_active: u64,
+    pub new_active: u64,
+    pub pending_stake_accounts: Vec<Pubkey>,
 }
 
 impl StakeManager {
     pub const POOL_SEED: &'static [u8] = b"pool_seed";
+    pub const RESERVE_SEED: &'static [u8] = b"reserve_seed";
+
+    pub const VERSION: u8 = 1;
 
     pub const DEFAULT_UNBONDING_DURATION: u64 = 2;
     pub const CAL_BASE: u64 = 1_000_000_000;
@@ -83,6 +191,100 @@ impl StakeManager {
     pub const DEFAULT_RATE_CHANGE_LIMIT: u64 = 500_000;
     pub const DEFAULT_STAKE_ACCOUNT_LEN_LIMIT: u64 = 100;
     pub const DEFAULT_SPLIT_ACCOUNT_LEN_LIMIT: u64 = 20;
+    pub const REWARD_PER_RSOL_BASE: u128 = 1_000_000_000_000_000_000;
+    pub const DEFAULT_INSTANT_UNSTAKE_MIN_FEE_COMMISSION: u64 = 3_000_000;
+    pub const DEFAULT_INSTANT_UNSTAKE_MAX_FEE_COMMISSION: u64 = 30_000_000;
+    pub const DEFAULT_MAX_VALIDATOR_COMMISSION: u8 = 10;
+    pub const DEFAULT_MIN_REBALANCE_AMOUNT: u64 = 1_000_000_000;
+
+    pub fn is_upgraded(&self) -> bool {
+        self.version == StakeManager::VERSION
+    }
+
+    pub fn advance_era_phase(&mut self) -> Result<()> {
+        let next_phase = self.era_process_data.next_phase();
+        require!(
+            self.era_phase.can_transition_to(next_phase),
+            Errors::EraPhaseTransitionInvalid
+        );
+
+        self.era_phase = next_phase;
+        Ok(())
+    }
+
+    // the era's tip budget is unbonded ahead into the tip fund like an unstake,
+    // and the protocol fee repays it at era_update_rate
+    pub fn fund_crank_tips(&mut self) {
+        let fund_target = if self.crank_tip > 0 {
+            self.crank_tip_era_budget
+        } else {
+            0
+        };
+
+        if self.crank_tip_fund < fund_target {
+            let amount = fund_target - self.crank_tip_fund;
+            self.era_unbond += amount;
+            self.active = self.active.saturating_sub(amount);
+            self.crank_tip_fund += amount;
+            self.crank_tip_unsettled += amount;
+        } else {
+            // a lowered budget returns the surplus to rsol holders as pool excess
+            let amount = self.crank_tip_fund - fund_target;
+            self.crank_tip_fund -= amount;
+            self.crank_tip_unsettled = self.crank_tip_unsettled.saturating_sub(amount);
+        }
+    }
+
+    // tip is skipped rather than failing the step when the budget, the fund or the pool cannot cover it,
+    // the pool never pays it out of lamports owed to bonds or unstake tickets
+    pub fn calc_crank_tip(&self, pool_balance: u64) -> u64 {
+        if self.crank_tip == 0
+            || self.crank_tip_era_paid + self.crank_tip > self.crank_tip_era_budget
+            || self.crank_tip_fund < self.crank_tip
+        {
+            return 0;
+        }
+
+        let available = pool_balance
+            .saturating_sub(self.rent_exempt_for_pool_acc)
+            .saturating_sub(self.era_process_data.need_bond)
+            .saturating_sub(self.calc_era_pool_bond())
+            .saturating_sub(self.total_unstake_unclaimed);
+        if available < self.crank_tip {
+            return 0;
+        }
+
+        self.crank_tip
+    }
+
+    pub fn record_crank_tip(&mut self, tip: u64) {
+        self.crank_tip_fund -= tip;
+        self.crank_tip_era_paid += tip;
+        self.total_crank_tip_paid += tip;
+    }
+
+    // stake deposits and reserve releases waiting in the pool to be bonded
+    pub fn calc_era_pool_bond(&self) -> u64 {
+        (self.era_bond + self.era_reserve_release).saturating_sub(self.era_reserve_fill)
+    }
+
+    // what unstake tickets can claim, the era's bond and the tip fund are already committed
+    pub fn calc_available_for_withdraw(&self, pool_balance: u64) -> u64 {
+        pool_balance
+            .saturating_sub(self.rent_exempt_for_pool_acc)
+            .saturating_sub(self.era_process_data.need_bond)
+            .saturating_sub(self.calc_era_pool_bond())
+            .saturating_sub(self.crank_tip_fund)
+    }
+
+    // pool balance beyond rent, unbonded stake deposits, unclaimed unstake tickets and the tip fund
+    pub fn calc_pool_excess(&self, pool_balance: u64) -> u64 {
+        pool_balance
+            .saturating_sub(self.rent_exempt_for_pool_acc)
+            .saturating_sub(self.calc_era_pool_bond())
+            .saturating_sub(self.total_unstake_unclaimed)
+            .saturating_sub(self.crank_tip_fund)
+    }
 
     pub fn calc_rsol_amount(&self, sol_amount: u64) -> Result<u64> {
         u64::try_from((sol_amount as u128) * (StakeManager::CAL_BASE as u128) / (self.rate as u128))
@@ -104,6 +306,34 @@ impl StakeManager {
         .map_err(|_| error!(Errors::CalculationFail))
     }
 
+    // utilization of the reserve, decimals 9
+    pub fn calc_reserve_utilization(&self, reserve_balance: u64) -> Result<u64> {
+        if self.reserve_target == 0 || reserve_balance >= self.reserve_target {
+            return Ok(0);
+        }
+
+        u64::try_from(
+            ((self.reserve_target - reserve_balance) as u128) * (StakeManager::CAL_BASE as u128)
+                / (self.reserve_target as u128),
+        )
+        .map_err(|_| error!(Errors::CalculationFail))
+    }
+
+    // fee commission rises linearly from min to max with the utilization left after the payout
+    pub fn calc_instant_unstake_fee(&self, sol_amount: u64) -> Result<u64> {
+        let utilization =
+            self.calc_reserve_utilization(self.reserve_balance.saturating_sub(sol_amount))?;
+        let fee_commission = (self.instant_unstake_min_fee_commission as u128)
+            + (self
+                .instant_unstake_max_fee_commission
+                .saturating_sub(self.instant_unstake_min_fee_commission) as u128)
+                * (utilization as u128)
+                / (StakeManager::CAL_BASE as u128);
+
+        u64::try_from((sol_amount as u128) * fee_commission / (StakeManager::CAL_BASE as u128))
+            .map_err(|_| error!(Errors::CalculationFail))
+    }
+
     pub fn calc_protocol_fee(&self, reward_sol: u64) -> Result<u64> {
         u64::try_from(
             (reward_sol as u128) * (self.protocol_fee_commission as u128) / (self.rate as u128),
@@ -111,6 +341,22 @@ impl StakeManager {
         .map_err(|_| error!(Errors::CalculationFail))
     }
 
+    // referrers share the protocol fee earned by the rsol still held in the token accounts they referred
+    pub fn calc_referral_fee(&self, protocol_fee: u64) -> Result<u64> {
+        if self.total_referred_rsol == 0 || self.total_rsol_supply == 0 {
+            return Ok(0);
+        }
+
+        u64::try_from(
+            (protocol_fee as u128)
+                * (self.referral_fee_share as u128)
+                * (self.total_referred_rsol.min(self.total_rsol_supply) as u128)
+                / (StakeManager::CAL_BASE as u128)
+                / (self.total_rsol_supply as u128),
+        )
+        .map_err(|_| error!(Errors::CalculationFail))
+    }
+
     pub fn calc_rate(&self, sol_amount: u64, rsol_amount: u64) -> Result<u64> {
         if sol_amount == 0 || rsol_amount == 0 {
             return Ok(StakeManager::CAL_BASE);
@@ -138,10 +384,826 @@ impl StakeManager {
 }
 
 #[account]
-#[derive(Debug)]
+#[derive(Debug, InitSpace)]
 pub struct UnstakeAccount {
     pub stake_manager: Pubkey,
     pub recipient: Pubkey,
     pub amount: u64,
     pub created_epoch: u64,
+    pub created_era: u64,
+    pub unstake_fee: u64,
+    pub pending_recipient: Pubkey,
+}
+
+impl UnstakeAccount {
+    pub const SEED: &'static [u8] = b"unstake_seed";
+}
+
+// unstake ticket layout before the upgrade, only read by upgrade_unstake_account
+#[derive(Clone, Debug, AnchorDeserialize)]
+pub struct LegacyUnstakeAccount {
+    pub stake_manager: Pubkey,
+    pub recipient: Pubkey,
+    pub amount: u64,
+    pub created_epoch: u64,
+}
+
+impl LegacyUnstakeAccount {
+    pub const LEN: usize = 32 + 32 + 8 + 8;
+}
+
+pub const MAX_OPEN_UNSTAKE_TICKETS: usize = 20;
+
+#[account]
+#[derive(Debug, InitSpace)]
+pub struct UnstakeIndex {
+    pub stake_manager: Pubkey,
+    pub owner: Pubkey,
+    pub next_ticket_id: u64,
+    #[max_len(MAX_OPEN_UNSTAKE_TICKETS)]
+    pub open_tickets: Vec<Pubkey>,
+}
+
+impl UnstakeIndex {
+    pub const SEED: &'static [u8] = b"unstake_index_seed";
+
+    // recipients of legacy tickets may never have had an index, there is nothing to remove then
+    pub fn remove_open_ticket(unstake_index: &AccountInfo, unstake_account: &Pubkey) -> Result<()> {
+        if *unstake_index.owner != crate::ID {
+            return Ok(());
+        }
+
+        let mut index = UnstakeIndex::try_deserialize(&mut &unstake_index.try_borrow_data()?[..])?;
+        index.open_tickets.retain(|e| e != unstake_account);
+        index.try_serialize(&mut &mut unstake_index.try_borrow_mut_data()?[..])
+    }
+}
+
+pub const MAX_VALIDATORS: usize = 64;
+
+#[derive(
+    Clone, Copy, Debug, Default, PartialEq, Eq, InitSpace, AnchorSerialize, AnchorDeserialize,
+)]
+pub enum ValidatorStatus {
+    #[default]
+    Active,
+    Draining,
+    Removed,
+    Delinquent,
+}
+
+#[derive(Clone, Debug, Default, InitSpace, AnchorSerialize, AnchorDeserialize)]
+pub struct ValidatorEntry {
+    pub vote_account: Pubkey,
+    pub weight: u64,
+    // delegated stake counted at the last era_update_active, adjusted by later unbonds and redelegations
+    pub active_stake: u64,
+    // stake activating or deactivating in the current era
+    pub transient_stake: u64,
+    // pool stake accounts delegated to the validator, rebuilt along with active_stake
+    pub stake_accounts_len: u64,
+    pub status: ValidatorStatus,
+    pub last_update_era: u64,
+}
+
+#[account]
+#[derive(Debug, InitSpace)]
+pub struct ValidatorRegistry {
+    pub stake_manager: Pubkey,
+    #[max_len(MAX_VALIDATORS)]
+    pub validators: Vec<ValidatorEntry>,
+}
+
+impl ValidatorRegistry {
+    pub const SEED: &'static [u8] = b"validator_registry_seed";
+    pub const DEFAULT_WEIGHT: u64 = 1;
+
+    pub fn get(&self, vote_account: &Pubkey) -> Option<&ValidatorEntry> {
+        self.validators
+            .iter()
+            .find(|e| e.vote_account == *vote_account)
+    }
+
+    pub fn get_mut(&mut self, vote_account: &Pubkey) -> Result<&mut ValidatorEntry> {
+        self.validators
+            .iter_mut()
+            .find(|e| e.vote_account == *vote_account)
+            .ok_or_else(|| error!(Errors::ValidatorNotExist))
+    }
+
+    pub fn weight(&self, vote_account: &Pubkey) -> u64 {
+        self.get(vote_account)
+            .filter(|e| e.status == ValidatorStatus::Active)
+            .map_or(0, |e| e.weight)
+    }
+
+    pub fn total_weight(&self) -> u64 {
+        self.validators
+            .iter()
+            .filter(|e| e.status == ValidatorStatus::Active)
+            .map(|e| e.weight)
+            .sum()
+    }
+
+    // weight of the validators that have neither bonded nor been skipped this era
+    pub fn remaining_bond_weight(&self, era_process_data: &EraProcessData) -> u64 {
+        self.validators
+            .iter()
+            .filter(|e| !era_process_data.is_bond_settled(&e.vote_account))
+            .map(|e| self.weight(&e.vote_account))
+            .sum()
+    }
+
+    // split what is left of the era's need bond by the remaining weight,
+    // so skipped validators' shares are spread over the others and the last one takes the rounding dust
+    pub fn calc_validator_bond(
+        &self,
+        era_process_data: &EraProcessData,
+        validator: &Pubkey,
+    ) -> Result<u64> {
+        let need_bond = era_process_data.need_bond;
+        if self.get(validator).map(|e| e.status) != Some(ValidatorStatus::Active)
+            || era_process_data.is_bond_settled(validator)
+        {
+            return Ok(0);
+        }
+
+        let remaining_weight = self.remaining_bond_weight(era_process_data);
+        if remaining_weight == 0 {
+            return Ok(need_bond);
+        }
+
+        let weight = self.weight(validator);
+        if weight == 0 {
+            return Ok(0);
+        }
+
+        if weight == remaining_weight {
+            return Ok(need_bond);
+        }
+
+        u64::try_from((need_bond as u128) * (weight as u128) / (remaining_weight as u128))
+            .map_err(|_| error!(Errors::CalculationFail))
+    }
+
+    // active stake is rebuilt from the stake accounts counted in each era's update active
+    pub fn update_active_stake(
+        &mut self,
+        vote_account: &Pubkey,
+        stake_amount: u64,
+        era: u64,
+    ) -> Result<()> {
+        let entry = self.get_mut(vote_account)?;
+        if entry.last_update_era != era {
+            entry.active_stake = 0;
+            entry.stake_accounts_len = 0;
+            entry.last_update_era = era;
+        }
+        entry.active_stake += stake_amount;
+        entry.stake_accounts_len += 1;
+
+        // stake found on a removed validator must still be drained
+        if entry.status == ValidatorStatus::Removed {
+            entry.status = ValidatorStatus::Draining;
+        }
+        Ok(())
+    }
+
+    // once every stake account is counted, an entry not updated this era has none left in the pool
+    pub fn clear_uncounted_stake(&mut self, era: u64) {
+        for entry in self
+            .validators
+            .iter_mut()
+            .filter(|e| e.last_update_era != era)
+        {
+            entry.active_stake = 0;
+            entry.stake_accounts_len = 0;
+            entry.last_update_era = era;
+            if entry.status == ValidatorStatus::Draining {
+                entry.status = ValidatorStatus::Removed;
+            }
+        }
+    }
+
+    pub fn add_activating_stake(&mut self, vote_account: &Pubkey, stake_amount: u64) -> Result<()> {
+        let entry = self.get_mut(vote_account)?;
+        entry.active_stake += stake_amount;
+        entry.transient_stake += stake_amount;
+        Ok(())
+    }
+
+    pub fn add_deactivating_stake(
+        &mut self,
+        vote_account: &Pubkey,
+        stake_amount: u64,
+    ) -> Result<()> {
+        let entry = self.get_mut(vote_account)?;
+        entry.active_stake = entry.active_stake.saturating_sub(stake_amount);
+        entry.transient_stake += stake_amount;
+        Ok(())
+    }
+
+    // deposits and stake withdrawals move active stake without anything activating
+    pub fn add_active_stake(&mut self, vote_account: &Pubkey, stake_amount: u64) -> Result<()> {
+        self.get_mut(vote_account)?.active_stake += stake_amount;
+        Ok(())
+    }
+
+    pub fn remove_active_stake(&mut self, vote_account: &Pubkey, stake_amount: u64) -> Result<()> {
+        let entry = self.get_mut(vote_account)?;
+        entry.active_stake = entry.active_stake.saturating_sub(stake_amount);
+        Ok(())
+    }
+
+    pub fn add_stake_account(&mut self, vote_account: &Pubkey) -> Result<()> {
+        self.get_mut(vote_account)?.stake_accounts_len += 1;
+        Ok(())
+    }
+
+    pub fn remove_stake_account(&mut self, vote_account: &Pubkey) -> Result<()> {
+        let entry = self.get_mut(vote_account)?;
+        entry.stake_accounts_len = entry.stake_accounts_len.saturating_sub(1);
+
+        // a draining validator is fully removed once the pool has no stake account left on it
+        if entry.status == ValidatorStatus::Draining && entry.stake_accounts_len == 0 {
+            entry.status = ValidatorStatus::Removed;
+        }
+        Ok(())
+    }
+
+    // based on the stake accounts rather than active_stake, which is only an estimate between updates
+    pub fn has_draining_stake(&self) -> bool {
+        self.validators
+            .iter()
+            .any(|e| e.status == ValidatorStatus::Draining && e.stake_accounts_len > 0)
+    }
+
+    // unbonds take stake from draining validators before any other
+    pub fn check_unbond_validator(&self, vote_account: &Pubkey) -> Result<()> {
+        let is_draining =
+            self.get(vote_account).map(|e| e.status) == Some(ValidatorStatus::Draining);
+        require!(
+            is_draining || !self.has_draining_stake(),
+            Errors::UnbondDrainingValidatorFirst
+        );
+        Ok(())
+    }
+
+    pub fn total_active_stake(&self) -> u64 {
+        self.validators.iter().map(|e| e.active_stake).sum()
+    }
+
+    // the validator's share of the pool's active stake by weight, zero once it stops being active
+    pub fn calc_target_stake(&self, vote_account: &Pubkey) -> Result<u64> {
+        let total_weight = self.total_weight();
+        if total_weight == 0 {
+            return Ok(0);
+        }
+
+        u64::try_from(
+            (self.total_active_stake() as u128) * (self.weight(vote_account) as u128)
+                / (total_weight as u128),
+        )
+        .map_err(|_| error!(Errors::CalculationFail))
+    }
+
+    // stake may only move from an over-allocated validator to an under-allocated one,
+    // at most the smaller of the two differences so neither crosses its target
+    pub fn calc_rebalance_limit(
+        &self,
+        from_validator: &Pubkey,
+        to_validator: &Pubkey,
+    ) -> Result<u64> {
+        let from_stake = self.get(from_validator).map_or(0, |e| e.active_stake);
+        let from_target = self.calc_target_stake(from_validator)?;
+        require_gt!(from_stake, from_target, Errors::ValidatorNotOverAllocated);
+
+        let to_stake = self.get(to_validator).map_or(0, |e| e.active_stake);
+        let to_target = self.calc_target_stake(to_validator)?;
+        require_gt!(to_target, to_stake, Errors::ValidatorNotUnderAllocated);
+
+        Ok((from_stake - from_target).min(to_target - to_stake))
+    }
+
+    pub fn clear_transient_stake(&mut self) {
+        for entry in self.validators.iter_mut() {
+            entry.transient_stake = 0;
+        }
+    }
+}
+
+pub const ERA_HISTORY_LEN: usize = 128;
+
+#[derive(Clone, Debug, Default, InitSpace, AnchorSerialize, AnchorDeserialize)]
+pub struct EraRecord {
+    pub era: u64,
+    pub rate: u64, // decimals 9
+    pub active: u64,
+    pub total_rsol_supply: u64,
+    pub reward: u64,
+    pub protocol_fee: u64,
+}
+
+#[account]
+#[derive(Debug, InitSpace)]
+pub struct EraHistory {
+    pub stake_manager: Pubkey,
+    pub next_index: u64,
+    #[max_len(ERA_HISTORY_LEN)]
+    pub records: Vec<EraRecord>,
+}
+
+impl EraHistory {
+    pub const SEED: &'static [u8] = b"era_history_seed";
+
+    // once full, the oldest record at next_index is overwritten
+    pub fn push(&mut self, record: EraRecord) {
+        let index = self.next_index as usize;
+        if index < self.records.len() {
+            self.records[index] = record;
+        } else {
+            self.records.push(record);
+        }
+        self.next_index = ((index + 1) % ERA_HISTORY_LEN) as u64;
+    }
+}
+
+#[account]
+#[derive(Debug, InitSpace)]
+pub struct ReferralAccount {
+    pub stake_manager: Pubkey,
+    pub referrer: Pubkey,
+    pub total_sol_referred: u64,
+    pub total_rsol_minted: u64,
+    // rsol still held in the token accounts this referrer brought in
+    pub referred_rsol: u64,
+    pub reward_per_rsol_checkpoint: u128, // decimals 18
+    pub pending_reward: u64,
+    pub total_reward_claimed: u64,
+}
+
+impl ReferralAccount {
+    pub const SEED: &'static [u8] = b"referral_seed";
+
+    // move rewards accrued since the last checkpoint into pending_reward
+    pub fn settle(&mut self, referral_reward_per_rsol: u128) -> Result<()> {
+        let reward = (self.referred_rsol as u128)
+            * (referral_reward_per_rsol - self.reward_per_rsol_checkpoint)
+            / StakeManager::REWARD_PER_RSOL_BASE;
+
+        self.pending_reward +=
+            u64::try_from(reward).map_err(|_| error!(Errors::CalculationFail))?;
+        self.reward_per_rsol_checkpoint = referral_reward_per_rsol;
+        Ok(())
+    }
+}
+
+#[account]
+#[derive(Debug, InitSpace)]
+pub struct ReferredStake {
+    pub stake_manager: Pubkey,
+    pub token_account: Pubkey,
+    pub referrer: Pubkey,
+    pub rsol_amount: u64,
+}
+
+impl ReferredStake {
+    pub const SEED: &'static [u8] = b"referred_stake_seed";
+
+    // the referred amount never exceeds what the token account still holds
+    pub fn release_over_balance(
+        &mut self,
+        rsol_balance: u64,
+        referral_account: &mut ReferralAccount,
+        stake_manager: &mut StakeManager,
+    ) -> Result<()> {
+        if self.rsol_amount <= rsol_balance {
+            return Ok(());
+        }
+
+        require_keys_eq!(
+            referral_account.referrer,
+            self.referrer,
+            Errors::ReferrerNotMatch
+        );
+        require_keys_eq!(
+            referral_account.stake_manager,
+            self.stake_manager,
+            Errors::ReferrerNotMatch
+        );
+
+        referral_account.settle(stake_manager.referral_reward_per_rsol)?;
+
+        let released = self.rsol_amount - rsol_balance;
+        referral_account.referred_rsol = referral_account.referred_rsol.saturating_sub(released);
+        stake_manager.total_referred_rsol =
+            stake_manager.total_referred_rsol.saturating_sub(released);
+        self.rsol_amount = rsol_balance;
+        Ok(())
+    }
+
+    // burns pass the token account's referred stake, it only exists when the account was staked with a referrer
+    pub fn sync(
+        referred_stake: &AccountInfo,
+        referral_account: Option<&mut ReferralAccount>,
+        stake_manager: &mut StakeManager,
+        rsol_balance: u64,
+    ) -> Result<()> {
+        if *referred_stake.owner != crate::ID {
+            return Ok(());
+        }
+
+        let mut referred =
+            ReferredStake::try_deserialize(&mut &referred_stake.try_borrow_data()?[..])?;
+        if referred.rsol_amount <= rsol_balance {
+            return Ok(());
+        }
+
+        let referral_account = referral_account.ok_or_else(|| error!(Errors::ReferrerNotMatch))?;
+        referred.release_over_balance(rsol_balance, referral_account, stake_manager)?;
+        referred.try_serialize(&mut &mut referred_stake.try_borrow_mut_data()?[..])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stake_manager() -> StakeManager {
+        StakeManager {
+            rate: StakeManager::CAL_BASE,
+            instant_unstake_min_fee_commission:
+                StakeManager::DEFAULT_INSTANT_UNSTAKE_MIN_FEE_COMMISSION,
+            instant_unstake_max_fee_commission:
+                StakeManager::DEFAULT_INSTANT_UNSTAKE_MAX_FEE_COMMISSION,
+            min_rebalance_amount: StakeManager::DEFAULT_MIN_REBALANCE_AMOUNT,
+            version: StakeManager::VERSION,
+            ..Default::default()
+        }
+    }
+
+    fn validator_registry(weights: &[u64]) -> ValidatorRegistry {
+        ValidatorRegistry {
+            stake_manager: Pubkey::new_unique(),
+            validators: weights
+                .iter()
+                .map(|&weight| ValidatorEntry {
+                    vote_account: Pubkey::new_unique(),
+                    weight,
+                    ..Default::default()
+                })
+                .collect(),
+        }
+    }
+
+    fn vote_accounts(validator_registry: &ValidatorRegistry) -> Vec<Pubkey> {
+        validator_registry
+            .validators
+            .iter()
+            .map(|e| e.vote_account)
+            .collect()
+    }
+
+    #[test]
+    fn test_instant_unstake_fee_no_utilization() {
+        let mut stake_manager = stake_manager();
+        stake_manager.reserve_target = 1_000_000_000_000;
+        stake_manager.reserve_balance = 1_100_000_000_000;
+
+        // the payout leaves the reserve at its target
+        let fee = stake_manager
+            .calc_instant_unstake_fee(100_000_000_000)
+            .unwrap();
+        assert_eq!(fee, 300_000_000);
+    }
+
+    #[test]
+    fn test_instant_unstake_fee_full_utilization() {
+        let mut stake_manager = stake_manager();
+        stake_manager.reserve_target = 1_000_000_000_000;
+        stake_manager.reserve_balance = 100_000_000_000;
+
+        // the payout empties the reserve
+        let fee = stake_manager
+            .calc_instant_unstake_fee(100_000_000_000)
+            .unwrap();
+        assert_eq!(fee, 3_000_000_000);
+    }
+
+    #[test]
+    fn test_instant_unstake_fee_half_utilization() {
+        let mut stake_manager = stake_manager();
+        stake_manager.reserve_target = 1_000_000_000_000;
+        stake_manager.reserve_balance = 600_000_000_000;
+
+        let fee = stake_manager
+            .calc_instant_unstake_fee(100_000_000_000)
+            .unwrap();
+        assert_eq!(fee, 1_650_000_000);
+    }
+
+    #[test]
+    fn test_instant_unstake_fee_no_reserve_target() {
+        let stake_manager = stake_manager();
+
+        let fee = stake_manager
+            .calc_instant_unstake_fee(100_000_000_000)
+            .unwrap();
+        assert_eq!(fee, 300_000_000);
+    }
+
+    #[test]
+    fn test_validator_bond_rounding_dust() {
+        let validator_registry = validator_registry(&[1, 1, 1]);
+        let validators = vote_accounts(&validator_registry);
+        let mut era_process_data = EraProcessData {
+            need_bond: 100,
+            ..Default::default()
+        };
+
+        let mut bonds = vec![];
+        for validator in &validators {
+            let bond = validator_registry
+                .calc_validator_bond(&era_process_data, validator)
+                .unwrap();
+            era_process_data.need_bond -= bond;
+            era_process_data.bonded_validators.push(*validator);
+            bonds.push(bond);
+        }
+        // the last validator takes the rounding dust
+        assert_eq!(bonds, vec![33, 33, 34]);
+        assert_eq!(era_process_data.need_bond, 0);
+    }
+
+    #[test]
+    fn test_validator_bond_by_weight() {
+        let validator_registry = validator_registry(&[1, 3]);
+        let validators = vote_accounts(&validator_registry);
+        let era_process_data = EraProcessData {
+            need_bond: 1_000,
+            ..Default::default()
+        };
+
+        let bond = validator_registry
+            .calc_validator_bond(&era_process_data, &validators[0])
+            .unwrap();
+        assert_eq!(bond, 250);
+        let bond = validator_registry
+            .calc_validator_bond(&era_process_data, &validators[1])
+            .unwrap();
+        assert_eq!(bond, 750);
+    }
+
+    #[test]
+    fn test_validator_bond_skipped_share() {
+        let validator_registry = validator_registry(&[1, 1, 2]);
+        let validators = vote_accounts(&validator_registry);
+        let era_process_data = EraProcessData {
+            need_bond: 1_000,
+            skipped_validators: vec![validators[2]],
+            ..Default::default()
+        };
+
+        // the skipped validator's share goes to the others
+        let bond = validator_registry
+            .calc_validator_bond(&era_process_data, &validators[0])
+            .unwrap();
+        assert_eq!(bond, 500);
+        let bond = validator_registry
+            .calc_validator_bond(&era_process_data, &validators[2])
+            .unwrap();
+        assert_eq!(bond, 0);
+    }
+
+    #[test]
+    fn test_validator_bond_zero_total_weight() {
+        let validator_registry = validator_registry(&[0, 0]);
+        let validators = vote_accounts(&validator_registry);
+        let era_process_data = EraProcessData {
+            need_bond: 1_000,
+            ..Default::default()
+        };
+
+        assert_eq!(validator_registry.total_weight(), 0);
+        let bond = validator_registry
+            .calc_validator_bond(&era_process_data, &validators[0])
+            .unwrap();
+        assert_eq!(bond, 1_000);
+    }
+
+    #[test]
+    fn test_validator_bond_zero_weight() {
+        let validator_registry = validator_registry(&[0, 1]);
+        let validators = vote_accounts(&validator_registry);
+        let era_process_data = EraProcessData {
+            need_bond: 1_000,
+            ..Default::default()
+        };
+
+        let bond = validator_registry
+            .calc_validator_bond(&era_process_data, &validators[0])
+            .unwrap();
+        assert_eq!(bond, 0);
+    }
+
+    #[test]
+    fn test_validator_bond_not_active() {
+        let mut validator_registry = validator_registry(&[1, 1]);
+        let validators = vote_accounts(&validator_registry);
+        let era_process_data = EraProcessData {
+            need_bond: 1_000,
+            ..Default::default()
+        };
+
+        for status in [
+            ValidatorStatus::Draining,
+            ValidatorStatus::Removed,
+            ValidatorStatus::Delinquent,
+        ] {
+            validator_registry.validators[0].status = status;
+            let bond = validator_registry
+                .calc_validator_bond(&era_process_data, &validators[0])
+                .unwrap();
+            assert_eq!(bond, 0);
+            // an inactive validator has no weight, the other takes the whole bond
+            let bond = validator_registry
+                .calc_validator_bond(&era_process_data, &validators[1])
+                .unwrap();
+            assert_eq!(bond, 1_000);
+        }
+
+        let bond = validator_registry
+            .calc_validator_bond(&era_process_data, &Pubkey::new_unique())
+            .unwrap();
+        assert_eq!(bond, 0);
+    }
+
+    #[test]
+    fn test_era_phase_transitions() {
+        let phases = [
+            EraPhase::Idle,
+            EraPhase::Bond,
+            EraPhase::Unbond,
+            EraPhase::UpdateActive,
+            EraPhase::UpdateRate,
+        ];
+        let illegal = [
+            (EraPhase::Bond, EraPhase::Idle),
+            (EraPhase::Unbond, EraPhase::Idle),
+            (EraPhase::Unbond, EraPhase::Bond),
+            (EraPhase::UpdateActive, EraPhase::Idle),
+            (EraPhase::UpdateActive, EraPhase::Bond),
+            (EraPhase::UpdateActive, EraPhase::Unbond),
+            (EraPhase::UpdateRate, EraPhase::Bond),
+            (EraPhase::UpdateRate, EraPhase::Unbond),
+            (EraPhase::UpdateRate, EraPhase::UpdateActive),
+        ];
+
+        for current in phases {
+            for next in phases {
+                assert_eq!(
+                    current.can_transition_to(next),
+                    !illegal.contains(&(current, next)),
+                    "{:?} -> {:?}",
+                    current,
+                    next
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_advance_era_phase_invalid() {
+        let mut stake_manager = stake_manager();
+        stake_manager.era_phase = EraPhase::UpdateActive;
+        stake_manager.era_process_data.need_bond = 1;
+
+        assert_eq!(
+            stake_manager.advance_era_phase().unwrap_err(),
+            error!(Errors::EraPhaseTransitionInvalid)
+        );
+        assert_eq!(stake_manager.era_phase, EraPhase::UpdateActive);
+    }
+
+    fn era_record(era: u64) -> EraRecord {
+        EraRecord {
+            era,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_era_history_push() {
+        let mut era_history = EraHistory {
+            stake_manager: Pubkey::new_unique(),
+            next_index: 0,
+            records: vec![],
+        };
+
+        for era in 0..3 {
+            era_history.push(era_record(era));
+        }
+        assert_eq!(era_history.records.len(), 3);
+        assert_eq!(era_history.next_index, 3);
+        assert_eq!(era_history.records[2].era, 2);
+    }
+
+    #[test]
+    fn test_era_history_push_wraparound() {
+        let mut era_history = EraHistory {
+            stake_manager: Pubkey::new_unique(),
+            next_index: 0,
+            records: vec![],
+        };
+
+        let len = ERA_HISTORY_LEN as u64;
+        for era in 0..len {
+            era_history.push(era_record(era));
+        }
+        assert_eq!(era_history.records.len(), ERA_HISTORY_LEN);
+        assert_eq!(era_history.next_index, 0);
+
+        // the oldest records are overwritten in place
+        era_history.push(era_record(len));
+        era_history.push(era_record(len + 1));
+        assert_eq!(era_history.records.len(), ERA_HISTORY_LEN);
+        assert_eq!(era_history.next_index, 2);
+        assert_eq!(era_history.records[0].era, len);
+        assert_eq!(era_history.records[1].era, len + 1);
+        assert_eq!(era_history.records[2].era, 2);
+        assert_eq!(era_history.records[ERA_HISTORY_LEN - 1].era, len - 1);
+    }
+
+    fn allocated_registry(active_stakes: &[u64]) -> ValidatorRegistry {
+        let mut validator_registry = validator_registry(&vec![1; active_stakes.len()]);
+        for (entry, &active_stake) in validator_registry.validators.iter_mut().zip(active_stakes) {
+            entry.active_stake = active_stake;
+        }
+        validator_registry
+    }
+
+    #[test]
+    fn test_rebalance_limit_at_min_rebalance_amount() {
+        let min_rebalance_amount = stake_manager().min_rebalance_amount;
+        let target = 10 * min_rebalance_amount;
+        let validator_registry =
+            allocated_registry(&[target + min_rebalance_amount, target - min_rebalance_amount]);
+        let validators = vote_accounts(&validator_registry);
+
+        let limit = validator_registry
+            .calc_rebalance_limit(&validators[0], &validators[1])
+            .unwrap();
+        assert_eq!(limit, min_rebalance_amount);
+    }
+
+    #[test]
+    fn test_rebalance_limit_below_min_rebalance_amount() {
+        let min_rebalance_amount = stake_manager().min_rebalance_amount;
+        let target = 10 * min_rebalance_amount;
+        let validator_registry = allocated_registry(&[
+            target + min_rebalance_amount - 1,
+            target - min_rebalance_amount + 1,
+        ]);
+        let validators = vote_accounts(&validator_registry);
+
+        let limit = validator_registry
+            .calc_rebalance_limit(&validators[0], &validators[1])
+            .unwrap();
+        assert_eq!(limit, min_rebalance_amount - 1);
+    }
+
+    #[test]
+    fn test_rebalance_limit_smaller_difference() {
+        let validator_registry = allocated_registry(&[1_500, 900, 600]);
+        let validators = vote_accounts(&validator_registry);
+
+        // target is 1_000 each, neither side crosses it
+        let limit = validator_registry
+            .calc_rebalance_limit(&validators[0], &validators[1])
+            .unwrap();
+        assert_eq!(limit, 100);
+        let limit = validator_registry
+            .calc_rebalance_limit(&validators[0], &validators[2])
+            .unwrap();
+        assert_eq!(limit, 400);
+    }
+
+    #[test]
+    fn test_rebalance_limit_not_allocated() {
+        let validator_registry = allocated_registry(&[1_000, 1_500, 500]);
+        let validators = vote_accounts(&validator_registry);
+
+        assert_eq!(
+            validator_registry
+                .calc_rebalance_limit(&validators[0], &validators[2])
+                .unwrap_err(),
+            error!(Errors::ValidatorNotOverAllocated)
+        );
+        assert_eq!(
+            validator_registry
+                .calc_rebalance_limit(&validators[1], &validators[0])
+                .unwrap_err(),
+            error!(Errors::ValidatorNotUnderAllocated)
+        );
+    }
 }
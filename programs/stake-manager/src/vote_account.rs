@@ -0,0 +1,265 @@
+use crate::Errors;
+use anchor_lang::prelude::*;
+use anchor_lang::solana_program::vote;
+
+// the fields read from a vote account, parsed by hand because vote state
+// deserialization is not available on chain
+#[derive(Debug)]
+pub struct VoteAccountState {
+    pub commission: u8,
+    pub last_credits_epoch: Option<u64>,
+}
+
+struct VoteDataReader<'a> {
+    data: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> VoteDataReader<'a> {
+    fn skip(&mut self, len: usize) -> Result<()> {
+        let end = self
+            .offset
+            .checked_add(len)
+            .ok_or_else(|| error!(Errors::InvalidVoteAccount))?;
+        require_gte!(self.data.len(), end, Errors::InvalidVoteAccount);
+        self.offset = end;
+        Ok(())
+    }
+
+    fn read_u8(&mut self) -> Result<u8> {
+        let start = self.offset;
+        self.skip(1)?;
+        Ok(self.data[start])
+    }
+
+    fn read_u32(&mut self) -> Result<u32> {
+        let start = self.offset;
+        self.skip(4)?;
+        Ok(u32::from_le_bytes(
+            self.data[start..self.offset].try_into().unwrap(),
+        ))
+    }
+
+    fn read_u64(&mut self) -> Result<u64> {
+        let start = self.offset;
+        self.skip(8)?;
+        Ok(u64::from_le_bytes(
+            self.data[start..self.offset].try_into().unwrap(),
+        ))
+    }
+
+    fn read_len(&mut self) -> Result<usize> {
+        usize::try_from(self.read_u64()?).map_err(|_| error!(Errors::InvalidVoteAccount))
+    }
+}
+
+impl VoteAccountState {
+    const VERSION_1_14_11: u32 = 1;
+    const VERSION_CURRENT: u32 = 2;
+    const LOCKOUT_LEN: usize = 12;
+    const LANDED_VOTE_LEN: usize = 13;
+    const AUTHORIZED_VOTER_LEN: usize = 40;
+    const PRIOR_VOTERS_LEN: usize = 32 * 48 + 8 + 1;
+    const EPOCH_CREDITS_LEN: usize = 24;
+
+    // any other version (the pre 1.14 layout, which uninitialized accounts also read as,
+    // or one newer than this program) is rejected rather than guessed at. such a validator
+    // can't be added and receives no new stake: era_skip_validator takes it out of the bond
+    // phase and redelegate and rebalance can't target it. stake already on it is still
+    // unbonded and updated, as those never read the vote account
+    pub fn parse(vote_account: &AccountInfo) -> Result<Self> {
+        require_keys_eq!(
+            *vote_account.owner,
+            vote::program::ID,
+            Errors::InvalidVoteAccount
+        );
+
+        let data = vote_account.try_borrow_data()?;
+        let mut reader = VoteDataReader {
+            data: &data,
+            offset: 0,
+        };
+
+        let vote_len = match reader.read_u32()? {
+            Self::VERSION_1_14_11 => Self::LOCKOUT_LEN,
+            Self::VERSION_CURRENT => Self::LANDED_VOTE_LEN,
+            _ => return err!(Errors::UnsupportedVoteStateVersion),
+        };
+
+        // node pubkey and authorized withdrawer
+        reader.skip(64)?;
+        let commission = reader.read_u8()?;
+
+        let votes_len = reader.read_len()?;
+        reader.skip(votes_len.saturating_mul(vote_len))?;
+
+        // root slot
+        if reader.read_u8()? == 1 {
+            reader.skip(8)?;
+        }
+
+        let authorized_voters_len = reader.read_len()?;
+        reader.skip(authorized_voters_len.saturating_mul(Self::AUTHORIZED_VOTER_LEN))?;
+        reader.skip(Self::PRIOR_VOTERS_LEN)?;
+
+        // epoch credits are ordered by epoch, the newest last
+        let epoch_credits_len = reader.read_len()?;
+        let last_credits_epoch = if epoch_credits_len == 0 {
+            None
+        } else {
+            reader.skip((epoch_credits_len - 1).saturating_mul(Self::EPOCH_CREDITS_LEN))?;
+            Some(reader.read_u64()?)
+        };
+
+        Ok(Self {
+            commission,
+            last_credits_epoch,
+        })
+    }
+
+    pub fn check_commission(&self, max_commission: u8) -> Result<()> {
+        require_gte!(
+            max_commission,
+            self.commission,
+            Errors::ValidatorCommissionTooHigh
+        );
+        Ok(())
+    }
+
+    // the validator must have earned credits in the current or the previous epoch
+    pub fn check_recent_credits(&self, epoch: u64) -> Result<()> {
+        let last_credits_epoch = self
+            .last_credits_epoch
+            .ok_or_else(|| error!(Errors::ValidatorNotVoting))?;
+        require_gte!(last_credits_epoch + 1, epoch, Errors::ValidatorNotVoting);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anchor_lang::solana_program::vote::state::{
+        VoteInit, VoteState, VoteState1_14_11, VoteStateVersions,
+    };
+
+    fn vote_state(commission: u8, credits_epochs: &[u64]) -> VoteState {
+        let mut vote_state = VoteState::new(
+            &VoteInit {
+                node_pubkey: Pubkey::new_unique(),
+                authorized_voter: Pubkey::new_unique(),
+                authorized_withdrawer: Pubkey::new_unique(),
+                commission,
+            },
+            &Clock::default(),
+        );
+        // enough votes to fill the tower and set a root slot
+        for slot in 1..=40 {
+            vote_state.process_next_vote_slot(slot, 0, slot);
+        }
+        // rooting earns credits in epoch 0, keep only the given epochs
+        vote_state.epoch_credits.clear();
+        for &epoch in credits_epochs {
+            vote_state.increment_credits(epoch, 1);
+        }
+        vote_state
+    }
+
+    fn serialize(versioned: &VoteStateVersions) -> Vec<u8> {
+        let mut data = vec![0; VoteState::size_of()];
+        VoteState::serialize(versioned, &mut data).unwrap();
+        data
+    }
+
+    fn parse(data: &mut [u8], owner: &Pubkey) -> Result<VoteAccountState> {
+        let key = Pubkey::new_unique();
+        let mut lamports = 0;
+        let account_info =
+            AccountInfo::new(&key, false, false, &mut lamports, data, owner, false, 0);
+        VoteAccountState::parse(&account_info)
+    }
+
+    #[test]
+    fn test_parse_current() {
+        let mut data = serialize(&VoteStateVersions::new_current(vote_state(
+            5,
+            &[10, 11, 12],
+        )));
+        let state = parse(&mut data, &vote::program::ID).unwrap();
+        assert_eq!(state.commission, 5);
+        assert_eq!(state.last_credits_epoch, Some(12));
+    }
+
+    #[test]
+    fn test_parse_1_14_11() {
+        let mut data = serialize(&VoteStateVersions::V1_14_11(Box::new(
+            VoteState1_14_11::from(vote_state(7, &[20, 21])),
+        )));
+        let state = parse(&mut data, &vote::program::ID).unwrap();
+        assert_eq!(state.commission, 7);
+        assert_eq!(state.last_credits_epoch, Some(21));
+    }
+
+    #[test]
+    fn test_parse_no_credits() {
+        let mut data = serialize(&VoteStateVersions::new_current(vote_state(0, &[])));
+        let state = parse(&mut data, &vote::program::ID).unwrap();
+        assert_eq!(state.last_credits_epoch, None);
+        assert_eq!(
+            state.check_recent_credits(1).unwrap_err(),
+            error!(Errors::ValidatorNotVoting)
+        );
+    }
+
+    #[test]
+    fn test_parse_unsupported_version() {
+        // an uninitialized account reads as the pre 1.14 layout
+        let mut data = vec![0; VoteState::size_of()];
+        assert_eq!(
+            parse(&mut data, &vote::program::ID).unwrap_err(),
+            error!(Errors::UnsupportedVoteStateVersion)
+        );
+
+        let mut data = serialize(&VoteStateVersions::new_current(vote_state(5, &[10])));
+        data[..4].copy_from_slice(&3u32.to_le_bytes());
+        assert_eq!(
+            parse(&mut data, &vote::program::ID).unwrap_err(),
+            error!(Errors::UnsupportedVoteStateVersion)
+        );
+    }
+
+    #[test]
+    fn test_parse_invalid_account() {
+        let mut data = serialize(&VoteStateVersions::new_current(vote_state(5, &[10])));
+        assert_eq!(
+            parse(&mut data, &Pubkey::new_unique()).unwrap_err(),
+            error!(Errors::InvalidVoteAccount)
+        );
+
+        let mut data = serialize(&VoteStateVersions::new_current(vote_state(5, &[10])));
+        // cut off inside the votes
+        assert_eq!(
+            parse(&mut data[..100], &vote::program::ID).unwrap_err(),
+            error!(Errors::InvalidVoteAccount)
+        );
+    }
+
+    #[test]
+    fn test_checks() {
+        let state = VoteAccountState {
+            commission: 10,
+            last_credits_epoch: Some(100),
+        };
+        assert!(state.check_commission(10).is_ok());
+        assert_eq!(
+            state.check_commission(9).unwrap_err(),
+            error!(Errors::ValidatorCommissionTooHigh)
+        );
+        assert!(state.check_recent_credits(100).is_ok());
+        assert!(state.check_recent_credits(101).is_ok());
+        assert_eq!(
+            state.check_recent_credits(102).unwrap_err(),
+            error!(Errors::ValidatorNotVoting)
+        );
+    }
+}